@@ -1,5 +1,7 @@
 mod tui;
 
+use regexer::{FileLoader, Flavor};
+
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
@@ -8,10 +10,34 @@ fn main() {
         return
     }
 
-    if args.len() > 1 {
-        let first_argument = &args[1];
-        let code = regexer::parse(first_argument.to_string());
+    let mut flavor = Flavor::default();
+    let mut pattern: Option<&String> = None;
+    let mut args_iter = args.iter().skip(1);
+
+    while let Some(arg) = args_iter.next() {
+        if arg == "--flavor" || arg == "-f" {
+            let name = args_iter.next().map(String::as_str).unwrap_or("");
+
+            flavor = match name.parse() {
+                Ok(flavor) => flavor,
+                Err(message) => {
+                    eprintln!("error: {}", message);
+                    std::process::exit(1);
+                }
+            };
+        } else {
+            pattern = Some(arg);
+        }
+    }
 
-        println!("{}", code);
+    if let Some(first_argument) = pattern {
+        // The CLI runs the user's own patterns, which may import files.
+        match regexer::compile(first_argument.to_string(), flavor, &FileLoader) {
+            Ok(regex) => println!("{}", regex),
+            Err(error) => {
+                eprintln!("{}", error.report(first_argument));
+                std::process::exit(1);
+            }
+        }
     }
 }
@@ -3,7 +3,7 @@ use anathema::core::{Event, KeyCode, Nodes};
 use anathema::values::{State, StateValue};
 use anathema::{runtime::Runtime, vm::Templates};
 
-use regexer::parse;
+use regexer::{compile, FileLoader, Flavor};
 
 #[derive(Debug, State)]
 struct RootState {
@@ -55,8 +55,13 @@ impl View for RootView {
                     }
                 }
                 KeyCode::Enter => {
-                    let regex = parse(self.state.input.to_string());
-                    *self.state.output = regex;
+                    let input = self.state.input.to_string();
+
+                    *self.state.output =
+                        match compile(input.clone(), Flavor::default(), &FileLoader) {
+                            Ok(regex) => regex.to_string(),
+                            Err(error) => error.report(&input),
+                        };
                 }
                 _ => {}
             }
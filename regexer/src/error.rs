@@ -0,0 +1,384 @@
+use std::fmt;
+
+use crate::lexer::ast::Reference;
+use crate::lexer::span::{Span, Spanned};
+use crate::transpiler::Flavor;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum ErrorKind {
+    // Lexing
+    InvalidToken(char),
+    InvalidNumber(String),
+    IntOverflow(String),
+    UnterminatedString,
+    InvalidEscape(String),
+
+    // Parsing
+    UnknownFunction {
+        name: String,
+        /// A known name close to it, probably the one meant.
+        suggestion: Option<String>,
+    },
+    UnexpectedToken(String),
+    UnclosedParen,
+    UnexpectedRightParen,
+    MisplacedFlags,
+
+    // Parameter validation
+    UnknownParameter {
+        function: String,
+        parameter: String,
+        suggestion: Option<String>,
+    },
+    RepeatedParameter {
+        function: String,
+        parameter: String,
+    },
+    PatternCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    ValueCount {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    EmptyLiteral,
+    InvalidRange {
+        start: char,
+        end: char,
+    },
+    NotACharacterClass {
+        function: String,
+    },
+    EmptyClass {
+        function: String,
+    },
+    NotASetOperand(String),
+    CannotNegate {
+        found: &'static str,
+    },
+    NotEnoughPatterns {
+        function: String,
+        minimum: usize,
+        found: usize,
+    },
+    ZeroWidthRepetition {
+        function: String,
+    },
+    CaptureInRepetition {
+        function: String,
+    },
+    ConflictingParameters {
+        function: String,
+        first: String,
+        second: String,
+    },
+    InvalidQuantifier {
+        min: u32,
+        max: u32,
+    },
+    ModeWithoutQuantifier {
+        function: String,
+    },
+    DuplicateGroupName(String),
+    BindingWithArguments(String),
+    RecursiveFunction(String),
+    DuplicateParameter {
+        function: String,
+        parameter: String,
+    },
+    UndefinedGroup(Reference),
+    MissingParameter {
+        function: String,
+        parameter: String,
+    },
+    InvalidParameterValue {
+        function: String,
+        parameter: String,
+        expected: String,
+    },
+
+    // Imports
+    ImportFailed {
+        path: String,
+        reason: String,
+    },
+    ImportCycle(String),
+    InModule {
+        path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    // Transpiling
+    EmptySet,
+    UncomputableSet,
+    VariableLookbehind {
+        flavor: Flavor,
+    },
+    Unsupported {
+        feature: &'static str,
+        flavor: Flavor,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub span: Span,
+    /// Second place of the source involved in the error, like the definition
+    /// of a function for an error at its call site.
+    pub note: Option<Box<Spanned<String>>>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, span: Span) -> Error {
+        return Error {
+            kind,
+            span,
+            note: None,
+        };
+    }
+
+    pub fn with_note(mut self, note: String, span: Span) -> Error {
+        self.note = Some(Box::new(Spanned::new(note, span)));
+
+        return self;
+    }
+
+    /// Renders the error together with the line of `source` it points at,
+    /// underlining the offending part, followed by the note if any.
+    pub fn report(&self, source: &str) -> String {
+        let mut report = format!("error: {}\n{}", self.kind, snippet(source, self.span));
+
+        if let Some(note) = &self.note {
+            report.push_str(&format!(
+                "\nnote: {}\n{}",
+                note.node,
+                snippet(source, note.span)
+            ));
+        }
+
+        return report;
+    }
+}
+
+fn snippet(source: &str, span: Span) -> String {
+    let line = source
+        .lines()
+        .nth(span.line.saturating_sub(1))
+        .unwrap_or("");
+    let line_number = span.line.to_string();
+    let padding = " ".repeat(line_number.len());
+
+    let column = span.column.saturating_sub(1);
+    let width = source
+        .get(span.start..span.end)
+        .map(|text| text.lines().next().unwrap_or("").chars().count())
+        .unwrap_or(0)
+        .max(1);
+
+    return format!(
+        "{padding}--> {}:{}\n{padding} |\n{line_number} | {line}\n{padding} | {}{}",
+        span.line,
+        span.column,
+        " ".repeat(column),
+        "^".repeat(width),
+    );
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKind::InvalidToken(symbol) => write!(f, "invalid token `{symbol}`"),
+            ErrorKind::InvalidNumber(number) => write!(f, "invalid number `{number}`"),
+            ErrorKind::IntOverflow(number) => {
+                write!(
+                    f,
+                    "number `{number}` is too large, the maximum is {}",
+                    u32::MAX
+                )
+            }
+            ErrorKind::UnterminatedString => {
+                write!(f, "unterminated string, missing the closing `\"`")
+            }
+            ErrorKind::InvalidEscape(sequence) => write!(f, "invalid escape sequence `{sequence}`"),
+            ErrorKind::UnknownFunction { name, suggestion } => {
+                write!(f, "unknown function `{name}`")?;
+                write_suggestion(f, suggestion)
+            }
+            ErrorKind::UnexpectedToken(token) => write!(f, "unexpected {token}"),
+            ErrorKind::UnclosedParen => write!(f, "unclosed `(`"),
+            ErrorKind::UnexpectedRightParen => {
+                write!(f, "unexpected `)` without a matching `(`")
+            }
+            ErrorKind::MisplacedFlags => {
+                write!(f, "`flags(...)` must come first in the pattern")
+            }
+            ErrorKind::UnknownParameter {
+                function,
+                parameter,
+                suggestion,
+            } => {
+                write!(f, "unknown parameter `{parameter}` for `{function}`")?;
+                write_suggestion(f, suggestion)
+            }
+            ErrorKind::RepeatedParameter {
+                function,
+                parameter,
+            } => write!(f, "`{parameter}` is given more than once to `{function}`"),
+            ErrorKind::PatternCount {
+                function,
+                expected,
+                found,
+            } => match expected {
+                0 => write!(f, "`{function}` doesn't take a pattern, found {found}"),
+                1 => write!(f, "`{function}` expects one pattern, found {found}"),
+                _ => write!(f, "`{function}` expects {expected} patterns, found {found}"),
+            },
+            ErrorKind::ValueCount {
+                function,
+                expected,
+                found,
+            } => match expected {
+                0 => write!(f, "`{function}` doesn't take a value, found {found}"),
+                1 => write!(f, "`{function}` expects one value, found {found}"),
+                _ => write!(f, "`{function}` expects {expected} values, found {found}"),
+            },
+            ErrorKind::EmptyLiteral => write!(f, "`literal` needs at least one character"),
+            ErrorKind::InvalidRange { start, end } => write!(
+                f,
+                "invalid range, `{start}` comes after `{end}`"
+            ),
+            ErrorKind::NotACharacterClass { function } => write!(
+                f,
+                "`{function}` only accepts strings and functions matching a single character, like `range` or `number`"
+            ),
+            ErrorKind::EmptyClass { function } => {
+                write!(f, "`{function}` needs at least one character")
+            }
+            ErrorKind::NotASetOperand(operator) => write!(
+                f,
+                "operands of {operator} must match a single character, like `range` or `number`"
+            ),
+            ErrorKind::CannotNegate { found } => {
+                write!(f, "`not` only negates a single character, found {found}")
+            }
+            ErrorKind::ZeroWidthRepetition { function } => write!(
+                f,
+                "`{function}` can't repeat a pattern matching no character, like an anchor or a lookaround"
+            ),
+            ErrorKind::CaptureInRepetition { function } => write!(
+                f,
+                "`{function}` writes its pattern more than once with `sep=`, so it can't hold a capturing group, use `group(..., capture=False)`"
+            ),
+            ErrorKind::NotEnoughPatterns {
+                function,
+                minimum,
+                found,
+            } => write!(
+                f,
+                "`{function}` expects at least {minimum} patterns, found {found}"
+            ),
+            ErrorKind::ConflictingParameters {
+                function,
+                first,
+                second,
+            } => write!(
+                f,
+                "parameters `{first}` and `{second}` of `{function}` can't be used together"
+            ),
+            ErrorKind::InvalidQuantifier { min, max } => {
+                write!(f, "the maximum ({max}) is smaller than the minimum ({min})")
+            }
+            ErrorKind::ModeWithoutQuantifier { function } => write!(
+                f,
+                "`{function}` needs a quantifier like `many=True` to be lazy, greedy or possessive"
+            ),
+            ErrorKind::DuplicateGroupName(name) => {
+                write!(f, "a group named `{name}` is already defined")
+            }
+            ErrorKind::BindingWithArguments(name) => {
+                write!(f, "`{name}` stands for a pattern and takes no arguments")
+            }
+            ErrorKind::DuplicateParameter {
+                function,
+                parameter,
+            } => {
+                write!(f, "`{function}` has more than one parameter named `{parameter}`")
+            }
+            ErrorKind::ImportFailed { path, reason } => {
+                write!(f, "can't import \"{path}\": {reason}")
+            }
+            ErrorKind::ImportCycle(path) => {
+                write!(f, "\"{path}\" ends up importing itself")
+            }
+            ErrorKind::InModule {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "in \"{path}\" at {line}:{column}: {message}"),
+            ErrorKind::RecursiveFunction(name) => {
+                write!(f, "`{name}` calls itself, which would never end")
+            }
+            ErrorKind::UndefinedGroup(reference) => match reference {
+                Reference::Number(number) => {
+                    write!(f, "no group {number} is defined before this reference")
+                }
+                Reference::Name(name) => {
+                    write!(f, "no group named `{name}` is defined before this reference")
+                }
+            },
+            ErrorKind::MissingParameter {
+                function,
+                parameter,
+            } => write!(f, "`{function}` requires the `{parameter}` parameter"),
+            ErrorKind::InvalidParameterValue {
+                function,
+                parameter,
+                expected,
+            } => write!(
+                f,
+                "parameter `{parameter}` of `{function}` expects {expected}"
+            ),
+            ErrorKind::EmptySet => write!(f, "this character class doesn't match any character"),
+            ErrorKind::UncomputableSet => write!(
+                f,
+                "Unicode classes can only be combined with `+`, `one_of` or `none_of`"
+            ),
+            ErrorKind::VariableLookbehind { flavor } => match flavor {
+                Flavor::Pcre => write!(
+                    f,
+                    "each branch of a lookbehind must have a fixed width in {flavor}"
+                ),
+                _ => write!(f, "a lookbehind must have a fixed width in {flavor}"),
+            },
+            ErrorKind::Unsupported { feature, flavor } => {
+                write!(f, "{feature} are not supported by {flavor}")
+            }
+        }
+    }
+}
+
+fn write_suggestion(f: &mut fmt::Formatter, suggestion: &Option<String>) -> fmt::Result {
+    return match suggestion {
+        Some(suggestion) => write!(f, ", did you mean `{suggestion}`?"),
+        None => Ok(()),
+    };
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at {}:{}",
+            self.kind, self.span.line, self.span.column
+        )
+    }
+}
+
+impl std::error::Error for Error {}
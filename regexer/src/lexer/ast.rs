@@ -1,305 +1,3978 @@
-use crate::lexer::tokens;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
-#[derive(Debug, Eq, PartialEq, Clone)]
+use crate::error::{Error, ErrorKind};
+use crate::lexer::span::{Span, Spanned};
+use crate::lexer::tokens::{self, Token};
+use crate::loader::{self, Loader, NoLoader};
+use crate::suggest;
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
 pub enum Casing {
     Upcase,
     Downcase,
+    /// Either case, the default.
+    #[default]
+    Any,
+}
+
+/// Which whitespace `whitespace(kind=...)` matches.
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy)]
+pub enum WhitespaceKind {
+    /// Any whitespace, the default.
+    #[default]
+    Any,
+    Space,
+    Tab,
+    Newline,
+}
+
+/// How a quantifier trades off between matching more and matching less.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Mode {
+    /// Matches as much as possible, giving back when the rest fails.
+    #[default]
+    Greedy,
+    /// Matches as little as possible.
+    Lazy,
+    /// Matches as much as possible and never gives back.
+    Possessive,
+}
+
+/// How many times a node repeats, `max` being `None` when unbounded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Quantifier {
+    pub min: u32,
+    pub max: Option<u32>,
+    pub mode: Mode,
+}
+
+impl Quantifier {
+    pub fn exactly(times: u32) -> Quantifier {
+        return Quantifier {
+            min: times,
+            max: Some(times),
+            mode: Mode::Greedy,
+        };
+    }
+
+    pub fn between(min: u32, max: u32) -> Quantifier {
+        return Quantifier {
+            min,
+            max: Some(max),
+            mode: Mode::Greedy,
+        };
+    }
+
+    pub fn at_least(min: u32) -> Quantifier {
+        return Quantifier {
+            min,
+            max: None,
+            mode: Mode::Greedy,
+        };
+    }
 }
 
+/// Parameters every quantifiable function accepts.
+const QUANTIFIER_PARAMETERS: [(&str, Type); 5] = [
+    ("select", Type::Count),
+    ("min", Type::Int),
+    ("max", Type::Int),
+    ("optional", Type::Bool),
+    ("many", Type::Bool),
+];
+
+/// Parameters choosing the [`Mode`] of a quantifier.
+const MODE_PARAMETERS: [(&str, Type); 3] = [
+    ("greedy", Type::Bool),
+    ("lazy", Type::Bool),
+    ("possessive", Type::Bool),
+];
+
+/// `select=`, `min=`/`max=` and `optional=`/`many=` are three ways to write
+/// a quantifier, so they can't be mixed, and a quantifier has one mode.
+const QUANTIFIER_CONFLICTS: [(&str, &str); 11] = [
+    ("select", "min"),
+    ("select", "max"),
+    ("select", "optional"),
+    ("select", "many"),
+    ("min", "optional"),
+    ("min", "many"),
+    ("max", "optional"),
+    ("max", "many"),
+    ("greedy", "lazy"),
+    ("greedy", "possessive"),
+    ("lazy", "possessive"),
+];
+
+/// Parameters of the `flags(...)` header, one per [`Flags`] field.
+const FLAG_PARAMETERS: [(&str, Type); 5] = [
+    ("multiline", Type::Bool),
+    ("dotall", Type::Bool),
+    ("ignore_case", Type::Bool),
+    ("extended", Type::Bool),
+    ("unicode", Type::Bool),
+];
+
+/// Values of `case=`.
+const CASE: Type = Type::Enum(&["upper", "lower", "any"]);
+
+/// Values of `kind=`.
+const WHITESPACE_KIND: Type = Type::Enum(&["space", "tab", "newline", "any"]);
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Function {
     Letter {
-        casing: Option<Casing>,
-        select: Option<u32>,
+        casing: Casing,
     },
-    Letters {
-        casing: Option<Casing>,
+    Glob,
+    Whitespace {
+        kind: WhitespaceKind,
     },
-    Glob {
-        rest: bool,
+    /// A word character, `\w`.
+    Word,
+    /// An ASCII letter or digit.
+    Alnum,
+    HexDigit,
+    /// An ASCII punctuation character.
+    Punctuation,
+    /// Any character, newlines included, unlike `glob`.
+    AnyChar,
+    Number,
+    Group(Vec<Node>),
+    /// Capturing group that can be referred to by name, e.g.
+    /// `group(number(select=4), name="year")`.
+    NamedGroup {
+        name: String,
+        body: Vec<Node>,
     },
-    Whitespace,
-    Number {
-        select: Option<u32>,
-    },
-    Numbers,
-    Group(Box<Vec<Function>>),
+    NonCapturingGroup(Vec<Node>),
+    /// Matches any of the branches, e.g. `either(letter, number)`.
+    Either(Vec<Vec<Node>>),
+    /// Start of the input.
+    Start,
+    /// End of the input.
+    End,
+    LineStart,
+    LineEnd,
+    WordBoundary,
+    NotWordBoundary,
+    /// Matches the whole input and nothing else, e.g. `exact(numbers)`.
+    Exact(Vec<Node>),
+    /// Matches the text as is, e.g. `literal("v1.2")`.
+    Literal(String),
+    /// Lookahead: the body must match next, without being consumed.
+    FollowedBy(Vec<Node>),
+    NotFollowedBy(Vec<Node>),
+    /// Lookbehind: the body must match right before, without being consumed.
+    PrecededBy(Vec<Node>),
+    NotPrecededBy(Vec<Node>),
+    /// Matches the same text as an earlier capturing group did.
+    SameAs(Reference),
+    /// Matches the body regardless of case.
+    IgnoreCase(Vec<Node>),
+    /// Any character with a Unicode property, e.g. `letter(unicode=True)`.
+    Property(Property),
+    /// Any character from `start` to `end`, both included.
+    Range(char, char),
+    /// Any character from the members, e.g. `one_of("-_.", range("a", "f"))`.
+    /// A [`Function::Literal`] member stands for each of its characters.
+    OneOf(Vec<Node>),
+    /// Any character but the members.
+    NoneOf(Vec<Node>),
+    /// Characters in both classes, e.g. `letter & one_of("abc")`.
+    Intersection(Box<Node>, Box<Node>),
+    /// Characters of the first class that aren't in the second, e.g.
+    /// `letter - one_of("aeiou")`.
+    Difference(Box<Node>, Box<Node>),
 }
 
-fn slice_until_end_func<'a>(
-    tokens: &'a Vec<tokens::Token>,
-    curr_idx: &'a usize,
-) -> (&'a [tokens::Token], usize) {
-    let tokens = &tokens[*curr_idx + 1..];
-    if let Some(right_paren_pos) = tokens
-        .iter()
-        .position(|item| *item == tokens::Token::RightParen)
-    {
-        return (&tokens[..right_paren_pos + 1], right_paren_pos);
+/// Flags applying to the whole regex, declared by a leading `flags(...)`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Flags {
+    /// `^` and `$` match at the ends of every line.
+    pub multiline: bool,
+    /// `.` also matches newlines.
+    pub dotall: bool,
+    pub ignore_case: bool,
+    /// The engine skips whitespace and `#` comments in the regex.
+    pub extended: bool,
+    pub unicode: bool,
+}
+
+/// The parsed source: its flags and the functions to match.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Pattern {
+    /// Span of the `flags(...)` header, if there is one.
+    pub flags: Spanned<Flags>,
+    pub nodes: Vec<Node>,
+}
+
+/// Unicode property matched by `letter` or `number` with `unicode=True`, or by
+/// `letter(script=...)`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Property {
+    Letter(Casing),
+    Number,
+    /// Name of a script, e.g. `Cyrillic`.
+    Script(String),
+}
+
+/// Capturing group a backreference points to.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Reference {
+    /// Groups are numbered from 1, in the order they open.
+    Number(u32),
+    Name(String),
+}
+
+impl Function {
+    /// Whether the function always matches exactly one character, so it can
+    /// be a member of a character class.
+    pub fn is_char_class(&self) -> bool {
+        if let Function::Literal(text) = self {
+            return text.chars().count() == 1;
+        }
+
+        return matches!(
+            self,
+            Function::Letter { .. }
+                | Function::Number
+                | Function::Whitespace { .. }
+                | Function::Word
+                | Function::Alnum
+                | Function::HexDigit
+                | Function::Punctuation
+                | Function::AnyChar
+                | Function::Property(_)
+                | Function::Range(..)
+                | Function::OneOf(_)
+                | Function::NoneOf(_)
+                | Function::Intersection(..)
+                | Function::Difference(..)
+        );
+    }
+}
+
+/// A [`Function`] together with the part of the source it was parsed from.
+///
+/// Equality ignores the span and the comments, so the same pattern written
+/// with different spacing produces equal trees.
+#[derive(Debug, Eq, Clone)]
+pub struct Node {
+    pub function: Function,
+    pub quantifier: Option<Quantifier>,
+    pub span: Span,
+    /// `#` comments written around the function, without the `#`.
+    pub comments: Vec<String>,
+}
+
+impl Node {
+    pub fn new(function: Function, span: Span) -> Node {
+        return Node {
+            function,
+            quantifier: None,
+            span,
+            comments: vec![],
+        };
     }
+}
+
+impl From<Function> for Node {
+    fn from(function: Function) -> Node {
+        return Node::new(function, Span::default());
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Node) -> bool {
+        return self.function == other.function && self.quantifier == other.quantifier;
+    }
+}
+
+/// Value given to a parameter, e.g. the `3` in `select=3`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Value {
+    Int(u32),
+    /// `2..5` or `2..`, both ends included.
+    Range(u32, Option<u32>),
+    Bool(bool),
+    String(String),
+    /// A name written without quotes, e.g. the `upper` in `case=upper`.
+    Identifier(String),
+    /// `[a, b]`.
+    List(Vec<Spanned<Value>>),
+    /// A pipeline given to a parameter declared as [`Type::Pattern`], e.g.
+    /// `sep=literal(",")`.
+    Pattern(Vec<Node>),
+}
 
-    return (&[], 0);
+/// What a parameter accepts.
+#[derive(Debug, Clone, Copy)]
+enum Type {
+    Int,
+    Bool,
+    /// An integer or a range.
+    Count,
+    /// A name, written as is or as a string, e.g. `script=Greek`.
+    Name,
+    /// One of the names, written as is or as a string, e.g. `case=upper`.
+    Enum(&'static [&'static str]),
+    /// A single value of the inner type, or a non-empty list of them.
+    List(&'static Type),
+    Pattern,
 }
 
-fn slice_until_end_group<'a>(
-    tokens: &'a Vec<tokens::Token>,
-    curr_idx: &'a usize,
-) -> (&'a [tokens::Token], usize) {
-    let tokens = &tokens[*curr_idx + 1..];
+impl Type {
+    /// Makes sure `value` is of this type, returning the span of the part
+    /// that isn't.
+    fn check(&self, value: &Spanned<Value>) -> Result<(), Span> {
+        let valid = match (self, &value.node) {
+            (Type::List(_), Value::List(values)) if values.is_empty() => false,
+            (Type::List(inner), Value::List(values)) => {
+                return values.iter().try_for_each(|value| inner.check(value));
+            }
+            (Type::List(inner), _) => return inner.check(value),
+            (Type::Enum(names), Value::Identifier(name) | Value::String(name)) => {
+                names.contains(&name.as_str())
+            }
+            (Type::Int, Value::Int(_))
+            | (Type::Bool, Value::Bool(_))
+            | (Type::Count, Value::Int(_) | Value::Range(..))
+            | (Type::Name, Value::Identifier(_) | Value::String(_))
+            | (Type::Pattern, Value::Pattern(_)) => true,
+            _ => false,
+        };
+
+        return if valid { Ok(()) } else { Err(value.span) };
+    }
+
+    /// Describes the values of this type, for error messages.
+    fn expected(&self) -> String {
+        return match self {
+            Type::Int => String::from("an integer"),
+            Type::Bool => String::from("`True` or `False`"),
+            Type::Count => String::from("an integer or a range like `2..5`"),
+            Type::Name => String::from("a name like `Greek` or a string"),
+            Type::Enum(names) => {
+                let names: Vec<String> = names.iter().map(|name| format!("`{name}`")).collect();
 
-    for (idx, range) in tokens.windows(2).enumerate() {
-        match range {
-            [tokens::Token::RightParen, tokens::Token::RightParen]
-            | [tokens::Token::Identifier(_), tokens::Token::RightParen] => {
-                return (&tokens[..idx + 1], idx);
+                match names.split_last() {
+                    Some((last, [])) => last.clone(),
+                    Some((last, rest)) => format!("{} or {last}", rest.join(", ")),
+                    None => String::from("nothing"),
+                }
             }
-            _ => continue,
-        }
+            Type::List(inner) => format!("{}, or a list of them", inner.expected()),
+            Type::Pattern => String::from("a pattern"),
+        };
     }
+}
 
-    return (&[], 0);
+/// The parameters a function accepts.
+struct Schema {
+    parameters: &'static [(&'static str, Type)],
+    /// Whether the quantifier and mode parameters are accepted too.
+    quantified: bool,
+    /// Pairs of parameters that can't be given together.
+    conflicts: &'static [(&'static str, &'static str)],
 }
 
-fn find_int_parameter(tokens: &[tokens::Token], parameter: String) -> Option<u32> {
-    let founded = tokens.windows(3).find(|window| {
-        matches!(
-            window,
-            [
-                tokens::Token::Parameter(param),
-                tokens::Token::Equal,
-                tokens::Token::Int(_),
-            ] if *param == parameter
-        )
-    });
+impl Schema {
+    fn new(parameters: &'static [(&'static str, Type)], quantified: bool) -> Schema {
+        return Schema {
+            parameters,
+            quantified,
+            conflicts: &[],
+        };
+    }
+
+    /// Every parameter accepted, the quantifier ones included.
+    fn parameters(&self) -> impl Iterator<Item = &(&'static str, Type)> {
+        let quantifier = QUANTIFIER_PARAMETERS
+            .iter()
+            .chain(&MODE_PARAMETERS)
+            .filter(|_| self.quantified);
+
+        return self.parameters.iter().chain(quantifier);
+    }
 
-    if let Some(inner_tokens) = founded {
-        if let [tokens::Token::Parameter(_), tokens::Token::Equal, tokens::Token::Int(i)] =
-            inner_tokens[..]
+    fn find(&self, parameter: &str) -> Option<Type> {
+        return self
+            .parameters()
+            .find(|(name, _)| *name == parameter)
+            .map(|(_, kind)| *kind);
+    }
+
+    fn conflict(&self, first: &str, second: &str) -> bool {
+        let quantifier = QUANTIFIER_CONFLICTS.iter().filter(|_| self.quantified);
+
+        return self
+            .conflicts
+            .iter()
+            .chain(quantifier)
+            .any(|&(a, b)| (a, b) == (first, second) || (b, a) == (first, second));
+    }
+}
+
+/// Names of the built-in functions, each with a [`schema`].
+const BUILTIN_FUNCTIONS: [&str; 39] = [
+    "letter",
+    "letters",
+    "number",
+    "numbers",
+    "whitespace",
+    "whitespaces",
+    "newline",
+    "tab",
+    "word",
+    "words",
+    "alnum",
+    "hex_digit",
+    "punctuation",
+    "any_char",
+    "glob",
+    "group",
+    "either",
+    "one_of_patterns",
+    "start",
+    "end",
+    "line_start",
+    "line_end",
+    "word_boundary",
+    "not_word_boundary",
+    "exact",
+    "literal",
+    "followed_by",
+    "not_followed_by",
+    "preceded_by",
+    "not_preceded_by",
+    "same_as",
+    "ignore_case",
+    "range",
+    "one_of",
+    "none_of",
+    "not",
+    "repeat",
+    "separated_by",
+    "flags",
+];
+
+/// The parameters of the built-in `function`, or `None` if there is no such
+/// function.
+fn schema(function: &str) -> Option<Schema> {
+    let schema = match function {
+        "letter" | "letters" => Schema {
+            parameters: &[
+                ("case", CASE),
+                ("unicode", Type::Bool),
+                ("script", Type::List(&Type::Name)),
+            ],
+            quantified: true,
+            conflicts: &[("script", "case"), ("script", "unicode")],
+        },
+        "number" | "numbers" => Schema::new(&[("unicode", Type::Bool)], true),
+        "whitespace" | "whitespaces" => {
+            Schema::new(&[("kind", Type::List(&WHITESPACE_KIND))], true)
+        }
+        "glob" => Schema::new(&[("rest", Type::Bool)], true),
+        "group" => Schema::new(&[("name", Type::Name), ("capture", Type::Bool)], true),
+        "newline" | "tab" | "word" | "words" | "alnum" | "hex_digit" | "punctuation"
+        | "any_char" | "either" | "one_of_patterns" | "literal" | "same_as" | "range"
+        | "one_of" | "none_of" | "not" | "ignore_case" => Schema::new(&[], true),
+        "start" | "end" | "line_start" | "line_end" | "word_boundary" | "not_word_boundary"
+        | "exact" | "followed_by" | "not_followed_by" | "preceded_by" | "not_preceded_by" => {
+            Schema::new(&[], false)
+        }
+        "repeat" => Schema::new(&[("times", Type::Count), ("sep", Type::Pattern)], false),
+        "separated_by" => Schema::new(
+            &[
+                ("sep", Type::Pattern),
+                ("min", Type::Int),
+                ("max", Type::Int),
+            ],
+            false,
+        ),
+        "flags" => Schema::new(&FLAG_PARAMETERS, false),
+        _ => return None,
+    };
+
+    return Some(schema);
+}
+
+fn is_quantifier_parameter(parameter: &str) -> bool {
+    return QUANTIFIER_PARAMETERS
+        .iter()
+        .any(|(name, _)| *name == parameter);
+}
+
+#[derive(Debug, Clone)]
+struct Parameter {
+    name: Spanned<String>,
+    value: Spanned<Value>,
+}
+
+/// Everything written between the parentheses of a function call: patterns
+/// and values given by position, and `name=value` parameters.
+#[derive(Debug, Default)]
+struct Arguments {
+    patterns: Vec<Vec<Node>>,
+    values: Vec<Spanned<Value>>,
+    parameters: Vec<Parameter>,
+}
+
+impl Arguments {
+    /// Checks the parameters against the `schema` of `function`: each one
+    /// must be known, given once, of the right type, and not conflict with
+    /// one given before it.
+    fn validate(&self, function: &str, schema: &Schema) -> Result<(), Error> {
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            let name = parameter.name.node.as_str();
+            let error = |kind| Error::new(kind, parameter.name.span);
+
+            let Some(kind) = schema.find(name) else {
+                return Err(error(ErrorKind::UnknownParameter {
+                    function: function.to_string(),
+                    parameter: name.to_string(),
+                    suggestion: suggest::closest(name, schema.parameters().map(|(name, _)| *name)),
+                }));
+            };
+
+            let earlier = &self.parameters[..index];
+
+            if earlier.iter().any(|param| param.name.node == name) {
+                return Err(error(ErrorKind::RepeatedParameter {
+                    function: function.to_string(),
+                    parameter: name.to_string(),
+                }));
+            }
+
+            if let Err(span) = kind.check(&parameter.value) {
+                return Err(invalid_value(function, name, &kind.expected(), span));
+            }
+
+            if let Some(other) = earlier
+                .iter()
+                .find(|param| schema.conflict(&param.name.node, name))
+            {
+                return Err(error(ErrorKind::ConflictingParameters {
+                    function: function.to_string(),
+                    first: other.name.node.clone(),
+                    second: name.to_string(),
+                }));
+            }
+        }
+
+        return Ok(());
+    }
+
+    fn find(&self, parameter: &str) -> Option<&Parameter> {
+        return self
+            .parameters
+            .iter()
+            .find(|param| param.name.node == parameter);
+    }
+
+    fn int(&self, function: &str, parameter: &str) -> Result<Option<u32>, Error> {
+        match self.find(parameter) {
+            Some(Parameter {
+                value:
+                    Spanned {
+                        node: Value::Int(i),
+                        ..
+                    },
+                ..
+            }) => Ok(Some(*i)),
+            Some(param) => Err(invalid_value(
+                function,
+                parameter,
+                "an integer",
+                param.value.span,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    fn bool(&self, function: &str, parameter: &str) -> Result<Option<bool>, Error> {
+        match self.find(parameter) {
+            Some(Parameter {
+                value:
+                    Spanned {
+                        node: Value::Bool(b),
+                        ..
+                    },
+                ..
+            }) => Ok(Some(*b)),
+            Some(param) => Err(invalid_value(
+                function,
+                parameter,
+                "`True` or `False`",
+                param.value.span,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads the quantifier parameters, once validated. They come in three
+    /// flavours: `select=3` or `select=2..5`, `min=`/`max=`, and
+    /// `optional=`/`many=`. Plural functions like `letters` default to
+    /// `many=True`.
+    fn quantifier(&self, function: &str, plural: bool) -> Result<Option<Quantifier>, Error> {
+        let given: Vec<&Parameter> = self
+            .parameters
+            .iter()
+            .filter(|param| is_quantifier_parameter(&param.name.node))
+            .collect();
+
+        let quantifier = match self.find("select").map(|param| &param.value) {
+            Some(Spanned {
+                node: Value::Int(times),
+                ..
+            }) => Some(Quantifier::exactly(*times)),
+            Some(Spanned {
+                node: Value::Range(min, max),
+                ..
+            }) => Some(Quantifier {
+                min: *min,
+                max: *max,
+                mode: Mode::Greedy,
+            }),
+            Some(value) => {
+                return Err(invalid_value(
+                    function,
+                    "select",
+                    "an integer or a range like `2..5`",
+                    value.span,
+                ));
+            }
+            None => {
+                let min = self.int(function, "min")?;
+                let max = self.int(function, "max")?;
+                let optional = self.bool(function, "optional")?.unwrap_or(false);
+                let many = self.bool(function, "many")?.unwrap_or(plural);
+
+                match (min, max, optional, many) {
+                    (None, None, false, false) => None,
+                    (None, None, true, false) => Some(Quantifier::between(0, 1)),
+                    (None, None, optional, true) => {
+                        Some(Quantifier::at_least(if optional { 0 } else { 1 }))
+                    }
+                    (min, max, _, _) => Some(Quantifier {
+                        min: min.unwrap_or(0),
+                        max,
+                        mode: Mode::Greedy,
+                    }),
+                }
+            }
+        };
+
+        if let Some(Quantifier {
+            min,
+            max: Some(max),
+            ..
+        }) = quantifier
         {
-            return Some(i);
+            if max < min {
+                let span = given
+                    .last()
+                    .map(|param| param.value.span)
+                    .unwrap_or_default();
+
+                return Err(Error::new(ErrorKind::InvalidQuantifier { min, max }, span));
+            }
         }
+
+        return Ok(quantifier);
     }
 
-    return None;
-}
+    /// Reads `greedy=`, `lazy=` or `possessive=`, once validated.
+    fn mode(&self, function: &str) -> Result<Option<Spanned<Mode>>, Error> {
+        let Some(parameter) = self.parameters.iter().find(|param| {
+            MODE_PARAMETERS
+                .iter()
+                .any(|(name, _)| *name == param.name.node)
+        }) else {
+            return Ok(None);
+        };
 
-fn find_casing_parameter(tokens: &[tokens::Token], parameter: String) -> Option<Casing> {
-    let upcase = tokens.windows(3).any(|window| {
-        matches!(
-            window,
-            [
-                tokens::Token::Parameter(param),
-                tokens::Token::Equal,
-                tokens::Token::True,
-            ] if *param == parameter
-        )
-    });
+        let name = parameter.name.node.as_str();
+        let enabled = self.bool(function, name)?.unwrap_or(false);
 
-    let downcase = tokens.windows(3).any(|window| {
-        matches!(
-            window,
-            [
-                tokens::Token::Parameter(param),
-                tokens::Token::Equal,
-                tokens::Token::False,
-            ] if *param == parameter
-        )
-    });
+        let mode = match (name, enabled) {
+            ("greedy", false) | ("lazy", true) => Mode::Lazy,
+            ("possessive", true) => Mode::Possessive,
+            _ => Mode::Greedy,
+        };
+
+        return Ok(Some(Spanned::new(mode, parameter.name.span)));
+    }
+
+    /// Reads a parameter written as a name or a string.
+    fn name(&self, function: &str, parameter: &str) -> Result<Option<Spanned<String>>, Error> {
+        return match self.find(parameter) {
+            Some(param) => name_of(function, parameter, &param.value).map(Some),
+            None => Ok(None),
+        };
+    }
+
+    /// Same as `name`, but also accepts a list of names.
+    fn names(&self, function: &str, parameter: &str) -> Result<Vec<Spanned<String>>, Error> {
+        let Some(param) = self.find(parameter) else {
+            return Ok(vec![]);
+        };
+
+        return match &param.value.node {
+            Value::List(values) => values
+                .iter()
+                .map(|value| name_of(function, parameter, value))
+                .collect(),
+            _ => Ok(vec![name_of(function, parameter, &param.value)?]),
+        };
+    }
+
+    fn pattern(&self, parameter: &str) -> Option<Vec<Node>> {
+        return match self.find(parameter).map(|param| &param.value.node) {
+            Some(Value::Pattern(nodes)) => Some(nodes.clone()),
+            _ => None,
+        };
+    }
+
+    /// Reads `case=upper|lower|any`, which defaults to any.
+    fn casing(&self, function: &str) -> Result<Casing, Error> {
+        let Some(case) = self.name(function, "case")? else {
+            return Ok(Casing::Any);
+        };
+
+        return match case.node.as_str() {
+            "upper" => Ok(Casing::Upcase),
+            "lower" => Ok(Casing::Downcase),
+            "any" => Ok(Casing::Any),
+            _ => Err(invalid_value(function, "case", &CASE.expected(), case.span)),
+        };
+    }
+
+    /// Takes the patterns given by position, making sure there are exactly
+    /// `count` of them.
+    fn patterns(
+        &mut self,
+        function: &str,
+        count: usize,
+        span: Span,
+    ) -> Result<Vec<Vec<Node>>, Error> {
+        if self.patterns.len() != count {
+            return Err(Error::new(
+                ErrorKind::PatternCount {
+                    function: function.to_string(),
+                    expected: count,
+                    found: self.patterns.len(),
+                },
+                span,
+            ));
+        }
+
+        return Ok(std::mem::take(&mut self.patterns));
+    }
+
+    /// Takes the values given by position, making sure there are exactly
+    /// `count` of them.
+    fn values(
+        &mut self,
+        function: &str,
+        count: usize,
+        span: Span,
+    ) -> Result<Vec<Spanned<Value>>, Error> {
+        if self.values.len() != count {
+            return Err(Error::new(
+                ErrorKind::ValueCount {
+                    function: function.to_string(),
+                    expected: count,
+                    found: self.values.len(),
+                },
+                self.values
+                    .get(count)
+                    .map(|value| value.span)
+                    .unwrap_or(span),
+            ));
+        }
 
-    if upcase && !downcase {
-        return Some(Casing::Upcase);
+        return Ok(std::mem::take(&mut self.values));
     }
 
-    if downcase && !upcase {
-        return Some(Casing::Downcase);
+    /// Same as `patterns`, but accepts any number of patterns from `minimum`.
+    fn patterns_from(
+        &mut self,
+        function: &str,
+        minimum: usize,
+        span: Span,
+    ) -> Result<Vec<Vec<Node>>, Error> {
+        if self.patterns.len() < minimum {
+            return Err(Error::new(
+                ErrorKind::NotEnoughPatterns {
+                    function: function.to_string(),
+                    minimum,
+                    found: self.patterns.len(),
+                },
+                span,
+            ));
+        }
+
+        return Ok(std::mem::take(&mut self.patterns));
     }
+}
 
-    return None;
+fn invalid_value(function: &str, parameter: &str, expected: &str, span: Span) -> Error {
+    return Error::new(
+        ErrorKind::InvalidParameterValue {
+            function: function.to_string(),
+            parameter: parameter.to_string(),
+            expected: expected.to_string(),
+        },
+        span,
+    );
 }
 
-// TODO: define a `consume` function to not keep repeating the peeks_tokens.next() all the time
-pub fn parse(tokens: Vec<tokens::Token>) -> Vec<Function> {
-    let mut peeks_tokens = tokens.clone().into_iter().enumerate().peekable();
-    let mut functions: Vec<Function> = vec![];
+fn name_of(
+    function: &str,
+    parameter: &str,
+    value: &Spanned<Value>,
+) -> Result<Spanned<String>, Error> {
+    return match &value.node {
+        Value::Identifier(name) | Value::String(name) => Ok(Spanned::new(name.clone(), value.span)),
+        _ => Err(invalid_value(
+            function,
+            parameter,
+            &Type::Name.expected(),
+            value.span,
+        )),
+    };
+}
 
-    while let Some((index, token)) = peeks_tokens.peek() {
-        if let tokens::Token::Identifier(identifier) = token {
-            match identifier.as_str() {
-                "letter" | "letters" => {
-                    let (func_tokens, right_pos_idx) = slice_until_end_func(&tokens, &index);
+fn describe(token: &Token) -> String {
+    return match token {
+        Token::Identifier(identifier) => format!("function `{identifier}`"),
+        Token::Parameter(parameter) => format!("parameter `{parameter}`"),
+        Token::Int(i) => format!("number `{i}`"),
+        Token::LeftParen => String::from("`(`"),
+        Token::RightParen => String::from("`)`"),
+        Token::LeftBracket => String::from("`[`"),
+        Token::RightBracket => String::from("`]`"),
+        Token::Whitespace => String::from("whitespace"),
+        Token::Equal => String::from("`=`"),
+        Token::False => String::from("`False`"),
+        Token::True => String::from("`True`"),
+        Token::Pipe => String::from("`|`"),
+        Token::Plus => String::from("`+`"),
+        Token::Minus => String::from("`-`"),
+        Token::Ampersand => String::from("`&`"),
+        Token::Comma => String::from("`,`"),
+        Token::Semicolon => String::from("`;`"),
+        Token::DotDot => String::from("`..`"),
+        Token::Let => String::from("`let`"),
+        Token::Def => String::from("`def`"),
+        Token::Import => String::from("`import`"),
+        Token::As => String::from("`as`"),
+        Token::Comment(_) => String::from("comment"),
+        Token::String(string) => format!("string \"{string}\""),
+    };
+}
 
-                    let casing = find_casing_parameter(func_tokens, "upcase".to_string());
-                    let select = find_int_parameter(func_tokens, "select".to_string());
+/// Function defined with `def`. Its body is kept as tokens and parsed again
+/// at each call, with the parameters bound to the arguments.
+#[derive(Debug, Clone)]
+struct Definition {
+    name: Spanned<String>,
+    parameters: Vec<String>,
+    body: Vec<Spanned<Token>>,
+    /// Bindings visible where the function is defined.
+    bindings: HashMap<String, Vec<Node>>,
+    /// Prefix of the functions the body can call, like `common.` for a
+    /// function imported as `common`.
+    namespace: String,
+    /// File the function is defined in, if imported.
+    path: Option<String>,
+    /// Comments written before `def`, given to the first node of each call.
+    comments: Vec<String>,
+}
 
-                    if identifier == "letter" {
-                        functions.push(Function::Letter {
-                            casing: casing.clone(),
-                            select: select.clone(),
-                        });
-                    }
+/// What an imported file makes available: its bindings and functions.
+struct Module {
+    bindings: HashMap<String, Vec<Node>>,
+    definitions: HashMap<String, Rc<Definition>>,
+}
+
+/// State shared by the parsers of every file of a pattern.
+struct Imports<'a> {
+    loader: &'a dyn Loader,
+    /// Files parsed so far.
+    cache: HashMap<PathBuf, Rc<Module>>,
+    /// Files being parsed, the importing ones first.
+    stack: Vec<PathBuf>,
+}
 
-                    if identifier == "letters" {
-                        functions.push(Function::Letters {
-                            casing: casing.clone(),
+/// Recursive-descent parser following the grammar in `docs/language.md`.
+struct Parser<'a> {
+    tokens: Vec<Spanned<Token>>,
+    position: usize,
+    /// Empty span right after the last token, used to report a missing token.
+    eof: Span,
+    /// Sub-patterns bound by `let`, which take precedence over built-ins.
+    bindings: HashMap<String, Vec<Node>>,
+    /// Functions defined with `def`, looked up when they are called so
+    /// that a function can call the ones defined after it.
+    definitions: HashMap<String, Rc<Definition>>,
+    /// Functions being expanded and where they were called, to catch
+    /// recursion.
+    calls: Vec<Spanned<String>>,
+    /// Prefix of the functions this parser can call.
+    namespace: String,
+    /// Comments met since the last node, for the node that follows.
+    leading: Vec<String>,
+    /// Comments met at the end of a line, for the node they follow.
+    trailing: Vec<String>,
+    /// File being parsed, `None` for the pattern itself.
+    path: Option<PathBuf>,
+    imports: &'a RefCell<Imports<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: Vec<Spanned<Token>>, imports: &'a RefCell<Imports<'a>>) -> Parser<'a> {
+        let eof = match tokens.last() {
+            Some(last) => Span::new(
+                last.span.end,
+                last.span.end,
+                last.span.line,
+                last.span.column + (last.span.end - last.span.start),
+            ),
+            None => Span::new(0, 0, 1, 1),
+        };
+
+        return Parser {
+            tokens,
+            position: 0,
+            eof,
+            bindings: HashMap::new(),
+            definitions: HashMap::new(),
+            calls: vec![],
+            namespace: String::new(),
+            leading: vec![],
+            trailing: vec![],
+            path: None,
+            imports,
+        };
+    }
+
+    /// Skips whitespace and comments. A comment on the same line as the token
+    /// before it is about what comes before, any other about what follows.
+    fn skip_whitespace(&mut self) {
+        while let Some(token) = self.tokens.get(self.position) {
+            match &token.node {
+                Token::Whitespace => {}
+                Token::Comment(text) => {
+                    let trailing = self.tokens[..self.position]
+                        .iter()
+                        .rev()
+                        .find(|previous| previous.node != Token::Whitespace)
+                        .is_some_and(|previous| {
+                            return !matches!(previous.node, Token::Comment(_))
+                                && previous.span.line == token.span.line;
                         });
-                    }
 
-                    peeks_tokens.nth(right_pos_idx + 1);
+                    match trailing {
+                        true => self.trailing.push(text.clone()),
+                        false => self.leading.push(text.clone()),
+                    }
                 }
-                "number" => {
-                    let (func_tokens, right_pos_idx) = slice_until_end_func(&tokens, &index);
+                _ => return,
+            }
 
-                    let select = find_int_parameter(func_tokens, "select".to_string());
+            self.position += 1;
+        }
+    }
 
-                    functions.push(Function::Number {
-                        select: select.clone(),
-                    });
+    /// Gives the comments on the line `nodes` end on to the last of them.
+    fn attach_trailing(&mut self, nodes: &mut [Node]) {
+        self.skip_whitespace();
 
-                    peeks_tokens.nth(right_pos_idx + 1);
-                }
-                "numbers" => {
-                    peeks_tokens.next();
-                    functions.push(Function::Numbers)
-                }
-                "glob" => {
-                    let (func_tokens, right_pos_idx) = slice_until_end_func(&tokens, &index);
-                    if func_tokens
-                        == [
-                            tokens::Token::LeftParen,
-                            tokens::Token::Parameter("rest".to_string()),
-                            tokens::Token::Equal,
-                            tokens::Token::True,
-                            tokens::Token::RightParen,
-                        ]
-                    {
-                        functions.push(Function::Glob { rest: true });
-                    }
+        if let Some(last) = nodes.last_mut() {
+            last.comments.append(&mut self.trailing);
+        }
+    }
 
-                    if func_tokens
-                        == [
-                            tokens::Token::LeftParen,
-                            tokens::Token::Parameter("rest".to_string()),
-                            tokens::Token::Equal,
-                            tokens::Token::False,
-                            tokens::Token::RightParen,
-                        ]
-                    {
-                        functions.push(Function::Glob { rest: false });
-                    }
+    /// Next token that is not whitespace, without consuming it.
+    fn peek(&mut self) -> Option<&Spanned<Token>> {
+        self.skip_whitespace();
 
-                    peeks_tokens.nth(right_pos_idx + 1);
-                }
-                "whitespace" => {
-                    peeks_tokens.next();
-                    functions.push(Function::Whitespace)
-                }
-                "group" => {
-                    let (group_tokens, right_pos_idx) = slice_until_end_group(&tokens, &index);
+        return self.tokens.get(self.position);
+    }
+
+    /// Same as `peek`, but looks past the next token.
+    fn peek_second(&mut self) -> Option<&Spanned<Token>> {
+        self.skip_whitespace();
 
-                    let tokens = parse(group_tokens.to_vec());
+        return self.tokens[(self.position + 1).min(self.tokens.len())..]
+            .iter()
+            .find(|token| !matches!(token.node, Token::Whitespace | Token::Comment(_)));
+    }
 
-                    functions.push(Function::Group(Box::new(tokens)));
+    fn consume(&mut self) -> Option<Spanned<Token>> {
+        self.skip_whitespace();
 
-                    peeks_tokens.nth(right_pos_idx + 1);
-                }
-                _ => {
-                    panic!("Invalid identifier -> [{identifier}]");
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+
+        return token;
+    }
+
+    fn unexpected(&mut self, expected: &str) -> Error {
+        let eof = self.eof;
+
+        return match self.peek() {
+            Some(token) => Error::new(
+                ErrorKind::UnexpectedToken(format!(
+                    "{}, expected {expected}",
+                    describe(&token.node)
+                )),
+                token.span,
+            ),
+            None => Error::new(
+                ErrorKind::UnexpectedToken(format!("end of input, expected {expected}")),
+                eof,
+            ),
+        };
+    }
+
+    fn parse_pattern(&mut self) -> Result<Pattern, Error> {
+        let mut pattern = Pattern::default();
+
+        self.parse_statements()?;
+
+        if let Some(Token::Identifier(identifier)) = self.peek().map(|token| &token.node) {
+            if identifier == "flags" {
+                pattern.flags = self.parse_flags()?;
+
+                match self.peek().map(|token| &token.node) {
+                    Some(Token::Pipe) => {
+                        self.consume();
+                    }
+                    None => return Ok(pattern),
+                    Some(_) => return Err(self.unexpected("`|`")),
                 }
             }
-        } else {
-            peeks_tokens.next();
+        }
+
+        if self.peek().is_none() {
+            return Ok(pattern);
+        }
+
+        pattern.nodes = self.parse_pipeline()?;
+
+        self.skip_whitespace();
+        if let Some(last) = pattern.nodes.last_mut() {
+            last.comments.append(&mut self.leading);
+        }
+
+        return match self.peek() {
+            None => Ok(pattern),
+            Some(Spanned {
+                node: Token::RightParen,
+                span,
+            }) => Err(Error::new(ErrorKind::UnexpectedRightParen, *span)),
+            Some(_) => Err(self.unexpected("`|`")),
+        };
+    }
+
+    /// Parses the `let`, `def` and `import` statements before the pattern.
+    fn parse_statements(&mut self) -> Result<(), Error> {
+        loop {
+            match self.peek().map(|token| &token.node) {
+                Some(Token::Let) => self.parse_binding()?,
+                Some(Token::Def) => self.parse_definition()?,
+                Some(Token::Import) => self.parse_import()?,
+                _ => return Ok(()),
+            }
         }
     }
 
-    return functions;
-}
+    /// Parses `let name = pipeline;`, a later binding shadowing an earlier one.
+    fn parse_binding(&mut self) -> Result<(), Error> {
+        self.consume();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::lexer::tokens;
+        let name = match self.peek().map(|token| &token.node) {
+            Some(Token::Identifier(name) | Token::Parameter(name)) if !name.contains('.') => {
+                name.clone()
+            }
+            _ => return Err(self.unexpected("a name")),
+        };
+        self.consume();
 
-    #[test]
-    fn test_grouped_tokens() {
-        let input = String::from(
-            "group(letters(upcase=True) | glob(rest=True)) | whitespace | group(numbers)",
-        );
+        let Some(Token::Equal) = self.peek().map(|token| &token.node) else {
+            return Err(self.unexpected("`=`"));
+        };
+        self.consume();
 
-        assert_eq!(
-            parse(tokens::tokenize(input)),
-            vec![
-                Function::Group(Box::new(vec![
-                    Function::Letters {
-                        casing: Some(Casing::Upcase),
-                    },
-                    Function::Glob { rest: true }
-                ])),
-                Function::Whitespace,
-                Function::Group(Box::new(vec![Function::Numbers]))
-            ]
-        );
+        let mut nodes = self.parse_pipeline()?;
+
+        let Some(Token::Semicolon) = self.peek().map(|token| &token.node) else {
+            return Err(self.unexpected("`|` or `;`"));
+        };
+        self.consume();
+        self.attach_trailing(&mut nodes);
+
+        self.definitions.remove(&name);
+        self.bindings.insert(name, nodes);
+
+        return Ok(());
     }
 
-    #[test]
-    fn test_basic_tokenize() {
-        let input = String::from(
-            "letter(upcase=True) | letter(upcase=False) | glob(rest=True) | glob(rest=False) | whitespace | number",
-        );
+    /// Parses `def name(parameter, ...) = pipeline;`. The body is only
+    /// checked when the function is called.
+    fn parse_definition(&mut self) -> Result<(), Error> {
+        let mut comments = std::mem::take(&mut self.leading);
+        self.consume();
 
-        assert_eq!(
-            parse(tokens::tokenize(input)),
-            vec![
-                Function::Letter {
-                    casing: Some(Casing::Upcase),
-                    select: None,
-                },
-                Function::Letter {
-                    casing: Some(Casing::Downcase),
-                    select: None
-                },
-                Function::Glob { rest: true },
-                Function::Glob { rest: false },
-                Function::Whitespace,
-                Function::Number { select: None },
-            ]
-        );
+        let name = match self.peek() {
+            Some(Spanned {
+                node: Token::Identifier(name),
+                span,
+            }) if !name.contains('.') => Spanned::new(name.clone(), *span),
+            _ => return Err(self.unexpected("a name")),
+        };
+        self.consume();
 
-        let input = String::from("letters(upcase=True) | glob(rest=True) | whitespace | numbers");
+        let Some(Token::LeftParen) = self.peek().map(|token| &token.node) else {
+            return Err(self.unexpected("`(`"));
+        };
+        let left_paren = self.consume().map(|token| token.span).unwrap_or(self.eof);
+        let mut parameters: Vec<String> = vec![];
 
-        assert_eq!(
-            parse(tokens::tokenize(input)),
-            vec![
-                Function::Letters {
-                    casing: Some(Casing::Upcase),
-                },
-                Function::Glob { rest: true },
-                Function::Whitespace,
-                Function::Numbers,
-            ]
+        loop {
+            let parameter = match self.peek() {
+                Some(Spanned {
+                    node: Token::RightParen,
+                    ..
+                }) => break,
+                Some(Spanned {
+                    node: Token::Identifier(parameter),
+                    span,
+                }) => Spanned::new(parameter.clone(), *span),
+                None => return Err(Error::new(ErrorKind::UnclosedParen, left_paren)),
+                Some(_) => return Err(self.unexpected("a parameter name")),
+            };
+            self.consume();
+
+            if parameters.contains(&parameter.node) {
+                return Err(Error::new(
+                    ErrorKind::DuplicateParameter {
+                        function: name.node.clone(),
+                        parameter: parameter.node,
+                    },
+                    parameter.span,
+                ));
+            }
+            parameters.push(parameter.node);
+
+            match self.peek().map(|token| &token.node) {
+                Some(Token::Comma) => {
+                    self.consume();
+                }
+                Some(Token::RightParen) => {}
+                None => return Err(Error::new(ErrorKind::UnclosedParen, left_paren)),
+                Some(_) => return Err(self.unexpected("`,` or `)`")),
+            }
+        }
+        self.consume();
+
+        let Some(Token::Equal) = self.peek().map(|token| &token.node) else {
+            return Err(self.unexpected("`=`"));
+        };
+        self.consume();
+
+        let mut body = vec![];
+        let mut depth = 0;
+
+        // Comments are part of the body, to be attached at each call.
+        while let Some(token) = self.tokens.get(self.position) {
+            match token.node {
+                Token::Semicolon if depth == 0 => break,
+                Token::LeftParen => depth += 1,
+                Token::RightParen => depth -= 1,
+                _ => {}
+            }
+
+            if token.node != Token::Whitespace {
+                body.push(token.clone());
+            }
+            self.position += 1;
+        }
+
+        if self.peek().is_none() {
+            return Err(self.unexpected("`;`"));
+        }
+
+        if body
+            .iter()
+            .all(|token| matches!(token.node, Token::Comment(_)))
+        {
+            return Err(self.unexpected("a function"));
+        }
+        self.consume();
+        self.skip_whitespace();
+        comments.append(&mut self.trailing);
+
+        self.bindings.remove(&name.node);
+        self.definitions.insert(
+            name.node.clone(),
+            Rc::new(Definition {
+                name,
+                parameters,
+                body,
+                bindings: self.bindings.clone(),
+                namespace: String::new(),
+                path: self.path.as_ref().map(|path| path.display().to_string()),
+                comments,
+            }),
         );
+
+        return Ok(());
     }
 
-    #[test]
-    fn test_select_parameters() {
-        let input = String::from(
-            "letter(select=3, upcase=True) | letters(upcase=False) | glob(rest=True) | glob(rest=False) | whitespace | number(select=99)",
-        );
+    /// Parses `import "path" as name;`, making the bindings and functions of
+    /// the file available as `name.binding`.
+    fn parse_import(&mut self) -> Result<(), Error> {
+        self.consume();
 
-        assert_eq!(
-            parse(tokens::tokenize(input)),
-            vec![
-                Function::Letter {
-                    casing: Some(Casing::Upcase),
-                    select: Some(3),
-                },
-                Function::Letters {
-                    casing: Some(Casing::Downcase),
-                },
-                Function::Glob { rest: true },
-                Function::Glob { rest: false },
-                Function::Whitespace,
-                Function::Number { select: Some(99) },
+        let (path, path_span) = match self.peek() {
+            Some(Spanned {
+                node: Token::String(path),
+                span,
+            }) => (path.clone(), *span),
+            _ => return Err(self.unexpected("a path")),
+        };
+        self.consume();
+
+        let Some(Token::As) = self.peek().map(|token| &token.node) else {
+            return Err(self.unexpected("`as`"));
+        };
+        self.consume();
+
+        let alias = match self.peek().map(|token| &token.node) {
+            Some(Token::Identifier(alias)) if !alias.contains('.') => alias.clone(),
+            _ => return Err(self.unexpected("a name")),
+        };
+        self.consume();
+
+        let Some(Token::Semicolon) = self.peek().map(|token| &token.node) else {
+            return Err(self.unexpected("`;`"));
+        };
+        self.consume();
+
+        // Imports aren't nodes, so their comments aren't kept.
+        self.skip_whitespace();
+        self.leading.clear();
+        self.trailing.clear();
+
+        let module = self.load_module(&path, path_span)?;
+
+        for (name, nodes) in &module.bindings {
+            let name = format!("{alias}.{name}");
+
+            self.definitions.remove(&name);
+            self.bindings.insert(name, nodes.clone());
+        }
+
+        for (name, definition) in &module.definitions {
+            let name = format!("{alias}.{name}");
+            let mut definition = Definition::clone(definition);
+            definition.namespace = format!("{alias}.{}", definition.namespace);
+
+            self.bindings.remove(&name);
+            self.definitions.insert(name, Rc::new(definition));
+        }
+
+        return Ok(());
+    }
+
+    /// Parses the file at `path`, relative to the file being parsed, unless
+    /// it was already imported.
+    fn load_module(&self, path: &str, span: Span) -> Result<Rc<Module>, Error> {
+        let directory = self
+            .path
+            .as_deref()
+            .and_then(Path::parent)
+            .unwrap_or(Path::new(""));
+        let resolved = loader::normalize(&directory.join(path));
+        let display = resolved.display().to_string();
+
+        if let Some(module) = self.imports.borrow().cache.get(&resolved) {
+            return Ok(module.clone());
+        }
+
+        if self.imports.borrow().stack.contains(&resolved) {
+            return Err(Error::new(ErrorKind::ImportCycle(display), span));
+        }
+
+        let text = self
+            .imports
+            .borrow()
+            .loader
+            .load(&resolved)
+            .map_err(|error| {
+                return Error::new(
+                    ErrorKind::ImportFailed {
+                        path: display.clone(),
+                        reason: error.to_string(),
+                    },
+                    span,
+                );
+            })?;
+
+        self.imports.borrow_mut().stack.push(resolved.clone());
+        let module = parse_module(text, resolved.clone(), self.imports);
+        self.imports.borrow_mut().stack.pop();
+
+        let module = Rc::new(module.map_err(|error| in_module(error, &display, span))?);
+        self.imports
+            .borrow_mut()
+            .cache
+            .insert(resolved, module.clone());
+
+        return Ok(module);
+    }
+
+    /// Expands a call to a function defined with `def`.
+    fn parse_call(
+        &mut self,
+        definition: Rc<Definition>,
+        identifier_span: Span,
+        comments: Vec<String>,
+    ) -> Result<Vec<Node>, Error> {
+        let name = &definition.name.node;
+        let key = format!("{}{name}", self.namespace);
+        let schema = Schema::new(&[], false);
+        let mut span = identifier_span;
+        let mut arguments = Arguments::default();
+
+        if let Some(Token::LeftParen) = self.peek().map(|token| &token.node) {
+            let (parsed, arguments_span) = self.parse_arguments(&schema)?;
+
+            arguments = parsed;
+            span = span.to(arguments_span);
+        }
+
+        if let Some(call) = self.calls.iter().find(|call| call.node == key) {
+            return Err(Error::new(ErrorKind::RecursiveFunction(name.clone()), span)
+                .with_note(format!("`{name}` is first called here"), call.span));
+        }
+
+        let defined_here = |error: Error| {
+            return error.with_note(format!("`{name}` is defined here"), definition.name.span);
+        };
+
+        arguments.validate(name, &schema).map_err(defined_here)?;
+        arguments.values(name, 0, span).map_err(defined_here)?;
+        let patterns = arguments
+            .patterns(name, definition.parameters.len(), span)
+            .map_err(defined_here)?;
+
+        let mut parser = Parser::new(definition.body.clone(), self.imports);
+        parser.bindings = definition.bindings.clone();
+        parser
+            .bindings
+            .extend(definition.parameters.iter().cloned().zip(patterns));
+        parser.definitions = self.definitions.clone();
+        parser.calls = self.calls.clone();
+        parser.calls.push(Spanned::new(key, span));
+        parser.namespace = definition.namespace.clone();
+        parser.leading = [comments, definition.comments.clone()].concat();
+
+        let called_here = |error: Error| {
+            return match (&definition.path, &error.note) {
+                (Some(path), _) => in_module(error, path, span),
+                (None, Some(_)) => error,
+                (None, None) => error.with_note(format!("in this call to `{name}`"), span),
+            };
+        };
+
+        let mut nodes = parser.parse_pipeline().map_err(called_here)?;
+
+        if parser.peek().is_some() {
+            return Err(called_here(parser.unexpected("`|`")));
+        }
+
+        match (&definition.path, nodes.as_mut_slice()) {
+            (Some(_), nodes) => respan(nodes, span),
+            (None, [node]) => node.span = span,
+            (None, _) => {}
+        }
+
+        return Ok(nodes);
+    }
+
+    /// Parses the `flags(...)` header.
+    fn parse_flags(&mut self) -> Result<Spanned<Flags>, Error> {
+        let identifier = self.consume().map(|token| token.span).unwrap_or(self.eof);
+
+        let Some(Token::LeftParen) = self.peek().map(|token| &token.node) else {
+            return Err(self.unexpected("`(`"));
+        };
+
+        let schema = Schema::new(&FLAG_PARAMETERS, false);
+        let (mut arguments, arguments_span) = self.parse_arguments(&schema)?;
+        let span = identifier.to(arguments_span);
+
+        arguments.validate("flags", &schema)?;
+        arguments.patterns("flags", 0, span)?;
+        arguments.values("flags", 0, span)?;
+
+        let flag = |name| -> Result<bool, Error> {
+            return Ok(arguments.bool("flags", name)?.unwrap_or(false));
+        };
+
+        let flags = Flags {
+            multiline: flag("multiline")?,
+            dotall: flag("dotall")?,
+            ignore_case: flag("ignore_case")?,
+            extended: flag("extended")?,
+            unicode: flag("unicode")?,
+        };
+
+        return Ok(Spanned::new(flags, span));
+    }
+
+    fn parse_pipeline(&mut self) -> Result<Vec<Node>, Error> {
+        let mut functions = self.parse_set()?;
+        self.attach_trailing(&mut functions);
+
+        while let Some(Token::Pipe) = self.peek().map(|token| &token.node) {
+            self.consume();
+
+            let mut set = self.parse_set()?;
+            self.attach_trailing(&mut set);
+            functions.append(&mut set);
+        }
+
+        return Ok(functions);
+    }
+
+    /// Parses unions and differences of character classes. Everything from
+    /// here down returns a sequence, as a binding can stand for several nodes.
+    fn parse_set(&mut self) -> Result<Vec<Node>, Error> {
+        let mut left = self.parse_intersection()?;
+
+        while let Some(Token::Plus | Token::Minus) = self.peek().map(|token| &token.node) {
+            let operator = self.consume().unwrap();
+            let right = self.parse_intersection()?;
+
+            left = set_operation(operator, left, right)?;
+        }
+
+        return Ok(left);
+    }
+
+    /// Parses intersections, which bind tighter than unions and differences.
+    fn parse_intersection(&mut self) -> Result<Vec<Node>, Error> {
+        let mut left = self.parse_operand()?;
+
+        while let Some(Token::Ampersand) = self.peek().map(|token| &token.node) {
+            let operator = self.consume().unwrap();
+            let right = self.parse_operand()?;
+
+            left = set_operation(operator, left, right)?;
+        }
+
+        return Ok(left);
+    }
+
+    fn parse_operand(&mut self) -> Result<Vec<Node>, Error> {
+        let Some(Token::LeftParen) = self.peek().map(|token| &token.node) else {
+            return self.parse_function();
+        };
+
+        let left_paren = self.consume().map(|token| token.span).unwrap_or(self.eof);
+        let mut nodes = self.parse_set()?;
+
+        return match self.peek() {
+            Some(Spanned {
+                node: Token::RightParen,
+                span,
+            }) => {
+                if let [node] = nodes.as_mut_slice() {
+                    node.span = left_paren.to(*span);
+                }
+                self.consume();
+
+                Ok(nodes)
+            }
+            None => Err(Error::new(ErrorKind::UnclosedParen, left_paren)),
+            Some(_) => Err(self.unexpected("`)`")),
+        };
+    }
+
+    fn parse_function(&mut self) -> Result<Vec<Node>, Error> {
+        let (identifier, identifier_span) = match self.peek() {
+            Some(Spanned {
+                node: Token::Identifier(identifier),
+                span,
+            }) => (identifier.clone(), *span),
+            _ => return Err(self.unexpected("a function")),
+        };
+        self.consume();
+
+        // Taken before the arguments, which would claim them otherwise.
+        let comments = std::mem::take(&mut self.leading);
+
+        if let Some(bound) = self.bindings.get(&identifier) {
+            let mut nodes = bound.clone();
+
+            if let Some(Token::LeftParen) = self.peek().map(|token| &token.node) {
+                return Err(Error::new(
+                    ErrorKind::BindingWithArguments(identifier),
+                    identifier_span,
+                ));
+            }
+
+            match nodes.as_mut_slice() {
+                nodes if identifier.contains('.') => respan(nodes, identifier_span),
+                [node] => node.span = identifier_span,
+                _ => {}
+            }
+
+            nodes[0].comments.splice(0..0, comments);
+
+            return Ok(nodes);
+        }
+
+        let key = format!("{}{identifier}", self.namespace);
+
+        if let Some(definition) = self.definitions.get(&key).cloned() {
+            return self.parse_call(definition, identifier_span, comments);
+        }
+
+        let Some(schema) = schema(&identifier) else {
+            return Err(self.unknown_function(identifier, identifier_span));
+        };
+
+        let mut span = identifier_span;
+        let mut arguments = Arguments::default();
+
+        if let Some(Token::LeftParen) = self.peek().map(|token| &token.node) {
+            let (parsed, arguments_span) = self.parse_arguments(&schema)?;
+
+            arguments = parsed;
+            span = span.to(arguments_span);
+        }
+
+        let name = Spanned::new(identifier, identifier_span);
+
+        if let "repeat" | "separated_by" = name.node.as_str() {
+            let mut nodes = build_repetition(&name.node, &schema, &mut arguments, span)?;
+            nodes[0].comments.splice(0..0, comments);
+
+            return Ok(nodes);
+        }
+        let (function, implicit) = build_function(&name, &schema, &mut arguments, span)?;
+
+        let mut node = Node::new(function, span);
+        node.comments = comments;
+        node.quantifier = arguments
+            .quantifier(&name.node, is_plural(&name.node))?
+            .or(implicit);
+
+        if let Some(mode) = arguments.mode(&name.node)? {
+            match &mut node.quantifier {
+                Some(quantifier) => quantifier.mode = mode.node,
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::ModeWithoutQuantifier {
+                            function: name.node.clone(),
+                        },
+                        mode.span,
+                    ));
+                }
+            }
+        }
+
+        return Ok(vec![node]);
+    }
+
+    /// Error for `name`, which is neither bound, defined nor built in,
+    /// suggesting the closest name that is.
+    fn unknown_function(&self, name: String, span: Span) -> Error {
+        let defined = self
+            .definitions
+            .keys()
+            .filter_map(|key| key.strip_prefix(self.namespace.as_str()));
+        let candidates = BUILTIN_FUNCTIONS
+            .into_iter()
+            .chain(self.bindings.keys().map(String::as_str))
+            .chain(defined);
+        let suggestion = suggest::closest(&name, candidates);
+
+        return Error::new(ErrorKind::UnknownFunction { name, suggestion }, span);
+    }
+
+    /// Parses `( argument, ... )`, returning the arguments and the span
+    /// going from `(` to `)`. The parameters `schema` declares as patterns
+    /// are read with `parse_pipeline` rather than `parse_value`.
+    fn parse_arguments(&mut self, schema: &Schema) -> Result<(Arguments, Span), Error> {
+        let left_paren = self.consume().map(|token| token.span).unwrap_or(self.eof);
+        let mut arguments = Arguments::default();
+
+        loop {
+            match self.peek().map(|token| token.node.clone()) {
+                None => return Err(Error::new(ErrorKind::UnclosedParen, left_paren)),
+                Some(Token::RightParen) => {
+                    let right_paren = self.consume().map(|token| token.span).unwrap_or(self.eof);
+
+                    return Ok((arguments, left_paren.to(right_paren)));
+                }
+                Some(_) => {}
+            }
+
+            self.parse_argument(&mut arguments, schema)?;
+
+            match self.peek().map(|token| &token.node) {
+                Some(Token::Comma) => {
+                    self.consume();
+                }
+                Some(Token::RightParen) => {}
+                None => return Err(Error::new(ErrorKind::UnclosedParen, left_paren)),
+                Some(_) => return Err(self.unexpected("`,` or `)`")),
+            }
+        }
+    }
+
+    fn parse_value(&mut self) -> Result<Spanned<Value>, Error> {
+        let value = match self.peek() {
+            Some(Spanned {
+                node: Token::Int(_),
+                ..
+            }) => self.parse_int_value()?,
+            Some(Spanned {
+                node: Token::True,
+                span,
+            }) => Spanned::new(Value::Bool(true), *span),
+            Some(Spanned {
+                node: Token::False,
+                span,
+            }) => Spanned::new(Value::Bool(false), *span),
+            Some(Spanned {
+                node: Token::String(string),
+                span,
+            }) => Spanned::new(Value::String(string.clone()), *span),
+            Some(Spanned {
+                node: Token::Identifier(name),
+                span,
+            }) => Spanned::new(Value::Identifier(name.clone()), *span),
+            Some(Spanned {
+                node: Token::LeftBracket,
+                ..
+            }) => return self.parse_list(),
+            _ => return Err(self.unexpected("a value")),
+        };
+        self.consume();
+
+        return Ok(value);
+    }
+
+    /// Parses `"[" [ value { "," value } ] "]"`.
+    fn parse_list(&mut self) -> Result<Spanned<Value>, Error> {
+        let left_bracket = self.consume().map(|token| token.span).unwrap_or(self.eof);
+        let mut values = vec![];
+
+        while !matches!(
+            self.peek().map(|token| &token.node),
+            Some(Token::RightBracket)
+        ) {
+            values.push(self.parse_value()?);
+
+            match self.peek().map(|token| &token.node) {
+                Some(Token::Comma) => {
+                    self.consume();
+                }
+                Some(Token::RightBracket) => {}
+                _ => return Err(self.unexpected("`,` or `]`")),
+            }
+        }
+
+        let right_bracket = self.consume().map(|token| token.span).unwrap_or(self.eof);
+
+        return Ok(Spanned::new(
+            Value::List(values),
+            left_bracket.to(right_bracket),
+        ));
+    }
+
+    /// Parses `INT [ ".." [ INT ] ]`, leaving the last token of the value
+    /// to be consumed by the caller.
+    fn parse_int_value(&mut self) -> Result<Spanned<Value>, Error> {
+        let (start, span) = match self.peek() {
+            Some(Spanned {
+                node: Token::Int(i),
+                span,
+            }) => (*i, *span),
+            _ => return Err(self.unexpected("an integer")),
+        };
+
+        if !matches!(
+            self.peek_second().map(|token| &token.node),
+            Some(Token::DotDot)
+        ) {
+            return Ok(Spanned::new(Value::Int(start), span));
+        }
+
+        self.consume();
+        let dots = self.peek().map(|token| token.span).unwrap_or(self.eof);
+
+        return match self.peek_second().cloned() {
+            Some(Spanned {
+                node: Token::Int(end),
+                span: end_span,
+            }) => {
+                self.consume();
+
+                Ok(Spanned::new(
+                    Value::Range(start, Some(end)),
+                    span.to(end_span),
+                ))
+            }
+            _ => Ok(Spanned::new(Value::Range(start, None), span.to(dots))),
+        };
+    }
+
+    fn parse_argument(&mut self, arguments: &mut Arguments, schema: &Schema) -> Result<(), Error> {
+        let is_parameter = match self.peek().map(|token| &token.node) {
+            Some(Token::Parameter(_)) => true,
+            Some(Token::Identifier(_)) => {
+                matches!(
+                    self.peek_second().map(|token| &token.node),
+                    Some(Token::Equal)
+                )
+            }
+            _ => false,
+        };
+
+        if !is_parameter {
+            match self.peek().map(|token| &token.node) {
+                Some(Token::String(_) | Token::Int(_) | Token::True | Token::False) => {
+                    arguments.values.push(self.parse_value()?);
+                }
+                _ => arguments.patterns.push(self.parse_pipeline()?),
+            }
+
+            return Ok(());
+        }
+
+        let name = match self.consume() {
+            Some(Spanned {
+                node: Token::Parameter(name) | Token::Identifier(name),
+                span,
+            }) => Spanned::new(name, span),
+            _ => unreachable!("checked by the lookahead above"),
+        };
+        self.consume();
+
+        let value = match schema.find(&name.node) {
+            Some(Type::Pattern) => {
+                let nodes = self.parse_pipeline()?;
+                let span = nodes[0].span.to(nodes[nodes.len() - 1].span);
+
+                Spanned::new(Value::Pattern(nodes), span)
+            }
+            _ => self.parse_value()?,
+        };
+
+        arguments.parameters.push(Parameter { name, value });
+
+        return Ok(());
+    }
+}
+
+fn single_char(function: &str, parameter: &str, value: Spanned<Value>) -> Result<char, Error> {
+    if let Value::String(string) = &value.node {
+        let mut chars = string.chars();
+
+        if let (Some(ch), None) = (chars.next(), chars.next()) {
+            return Ok(ch);
+        }
+    }
+
+    return Err(invalid_value(
+        function,
+        parameter,
+        "a single character",
+        value.span,
+    ));
+}
+
+/// Turns the strings and single-character patterns given to `one_of` or
+/// `none_of` into the members of the class.
+fn class_members(
+    function: &str,
+    arguments: &mut Arguments,
+    span: Span,
+) -> Result<Vec<Node>, Error> {
+    let mut members = vec![];
+
+    for value in std::mem::take(&mut arguments.values) {
+        match value.node {
+            Value::String(string) if !string.is_empty() => {
+                members.push(Node::new(Function::Literal(string), value.span));
+            }
+            _ => {
+                return Err(invalid_value(
+                    function,
+                    "member",
+                    "a non-empty string",
+                    value.span,
+                ))
+            }
+        }
+    }
+
+    for pattern in std::mem::take(&mut arguments.patterns) {
+        for node in pattern {
+            if !node.function.is_char_class() || node.quantifier.is_some() {
+                return Err(Error::new(
+                    ErrorKind::NotACharacterClass {
+                        function: function.to_string(),
+                    },
+                    node.span,
+                ));
+            }
+
+            members.push(node);
+        }
+    }
+
+    if members.is_empty() {
+        return Err(Error::new(
+            ErrorKind::EmptyClass {
+                function: function.to_string(),
+            },
+            span,
+        ));
+    }
+
+    return Ok(members);
+}
+
+/// Tells what `not` was given when it isn't a single character.
+fn negated_kind(function: &Function) -> Option<&'static str> {
+    if let Function::Literal(_) = function {
+        return Some("a `literal`, use `none_of` to exclude characters");
+    }
+
+    if function.is_char_class() {
+        return None;
+    }
+
+    return Some(match function {
+        Function::Group(_) | Function::NamedGroup { .. } | Function::NonCapturingGroup(_) => {
+            "a group"
+        }
+        Function::Either(_) => "an alternation",
+        Function::Exact(_) => "an anchored pattern",
+        Function::Glob => "`glob`, which already matches any character",
+        Function::FollowedBy(_)
+        | Function::NotFollowedBy(_)
+        | Function::PrecededBy(_)
+        | Function::NotPrecededBy(_) => "a lookaround",
+        Function::SameAs(_) => "a backreference",
+        Function::IgnoreCase(_) => "`ignore_case`, negate its body instead",
+        _ => "an anchor",
+    });
+}
+
+/// Combines two character classes with `+`, `-` or `&`.
+fn set_operation(
+    operator: Spanned<Token>,
+    left: Vec<Node>,
+    right: Vec<Node>,
+) -> Result<Vec<Node>, Error> {
+    let left = set_operand(&operator, left)?;
+    let right = set_operand(&operator, right)?;
+
+    let span = left.span.to(right.span);
+    let function = match operator.node {
+        Token::Plus => Function::OneOf(vec![left, right]),
+        Token::Minus => Function::Difference(Box::new(left), Box::new(right)),
+        _ => Function::Intersection(Box::new(left), Box::new(right)),
+    };
+
+    return Ok(vec![Node::new(function, span)]);
+}
+
+/// Makes sure `nodes` is a single, unquantified character class.
+fn set_operand(operator: &Spanned<Token>, mut nodes: Vec<Node>) -> Result<Node, Error> {
+    let error = |span| Error::new(ErrorKind::NotASetOperand(describe(&operator.node)), span);
+
+    return match nodes.as_slice() {
+        [node] if node.function.is_char_class() && node.quantifier.is_none() => Ok(nodes.remove(0)),
+        [node] => Err(error(node.span)),
+        [first, .., last] => Err(error(first.span.to(last.span))),
+        [] => unreachable!("a pipeline has at least one function"),
+    };
+}
+
+fn is_group_name(name: &str) -> bool {
+    return name.starts_with(|ch: char| ch.is_ascii_alphabetic() || ch == '_')
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+}
+
+fn is_script_name(name: &str) -> bool {
+    return !name.is_empty() && name.chars().all(|ch| ch.is_ascii_alphabetic() || ch == '_');
+}
+
+fn is_plural(identifier: &str) -> bool {
+    return matches!(identifier, "letters" | "numbers" | "words" | "whitespaces");
+}
+
+/// Builds the function called `name`, also returning the quantifier it
+/// implies when no quantifier parameter is given, e.g. `*` for
+/// `glob(rest=True)`.
+fn build_function(
+    name: &Spanned<String>,
+    schema: &Schema,
+    arguments: &mut Arguments,
+    span: Span,
+) -> Result<(Function, Option<Quantifier>), Error> {
+    let identifier = name.node.as_str();
+
+    if !matches!(
+        identifier,
+        "group"
+            | "either"
+            | "one_of_patterns"
+            | "exact"
+            | "one_of"
+            | "none_of"
+            | "not"
+            | "followed_by"
+            | "not_followed_by"
+            | "preceded_by"
+            | "not_preceded_by"
+            | "ignore_case"
+    ) {
+        arguments.patterns(identifier, 0, span)?;
+    }
+
+    if !matches!(
+        identifier,
+        "literal" | "range" | "one_of" | "none_of" | "same_as"
+    ) {
+        arguments.values(identifier, 0, span)?;
+    }
+
+    arguments.validate(identifier, schema)?;
+
+    match identifier {
+        "letter" | "letters" => {
+            let casing = arguments.casing(identifier)?;
+            let unicode = arguments.bool(identifier, "unicode")?;
+            let mut scripts = vec![];
+
+            for script in arguments.names(identifier, "script")? {
+                if !is_script_name(&script.node) {
+                    return Err(invalid_value(
+                        identifier,
+                        "script",
+                        "a script name like `Cyrillic`",
+                        script.span,
+                    ));
+                }
+
+                let property = Function::Property(Property::Script(script.node));
+                scripts.push(Node::new(property, script.span));
+            }
+
+            if !scripts.is_empty() {
+                return Ok((any_of(scripts), None));
+            }
+
+            if unicode == Some(true) {
+                return Ok((Function::Property(Property::Letter(casing)), None));
+            }
+
+            return Ok((Function::Letter { casing }, None));
+        }
+        "number" | "numbers" => {
+            if arguments.bool(identifier, "unicode")? == Some(true) {
+                return Ok((Function::Property(Property::Number), None));
+            }
+
+            return Ok((Function::Number, None));
+        }
+        "whitespace" | "whitespaces" => {
+            let mut kinds = vec![];
+
+            for kind in arguments.names(identifier, "kind")? {
+                let whitespace = match kind.node.as_str() {
+                    "any" => WhitespaceKind::Any,
+                    "space" => WhitespaceKind::Space,
+                    "tab" => WhitespaceKind::Tab,
+                    "newline" => WhitespaceKind::Newline,
+                    _ => {
+                        return Err(invalid_value(
+                            identifier,
+                            "kind",
+                            &WHITESPACE_KIND.expected(),
+                            kind.span,
+                        ));
+                    }
+                };
+
+                kinds.push(Node::new(
+                    Function::Whitespace { kind: whitespace },
+                    kind.span,
+                ));
+            }
+
+            if kinds.is_empty() {
+                return Ok((
+                    Function::Whitespace {
+                        kind: WhitespaceKind::Any,
+                    },
+                    None,
+                ));
+            }
+
+            return Ok((any_of(kinds), None));
+        }
+        "newline" | "tab" => {
+            let kind = match identifier {
+                "newline" => WhitespaceKind::Newline,
+                _ => WhitespaceKind::Tab,
+            };
+
+            return Ok((Function::Whitespace { kind }, None));
+        }
+        "word" | "words" => {
+            return Ok((Function::Word, None));
+        }
+        "alnum" | "hex_digit" | "punctuation" | "any_char" => {
+            let function = match identifier {
+                "alnum" => Function::Alnum,
+                "hex_digit" => Function::HexDigit,
+                "punctuation" => Function::Punctuation,
+                _ => Function::AnyChar,
+            };
+
+            return Ok((function, None));
+        }
+        "glob" => {
+            let rest = match arguments.bool(identifier, "rest")? {
+                Some(rest) => rest,
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::MissingParameter {
+                            function: identifier.to_string(),
+                            parameter: "rest".to_string(),
+                        },
+                        span,
+                    ));
+                }
+            };
+
+            if let (true, Some(other)) = (
+                rest,
+                arguments
+                    .parameters
+                    .iter()
+                    .find(|param| is_quantifier_parameter(&param.name.node)),
+            ) {
+                return Err(Error::new(
+                    ErrorKind::ConflictingParameters {
+                        function: identifier.to_string(),
+                        first: "rest".to_string(),
+                        second: other.name.node.clone(),
+                    },
+                    other.name.span,
+                ));
+            }
+
+            return Ok((Function::Glob, rest.then(|| Quantifier::at_least(0))));
+        }
+        "group" => {
+            let body = arguments.patterns(identifier, 1, span)?.remove(0);
+            let name = arguments.name(identifier, "name")?;
+            let capture = arguments.bool(identifier, "capture")?;
+
+            return match (name, capture) {
+                (Some(name), Some(false)) => Err(Error::new(
+                    ErrorKind::ConflictingParameters {
+                        function: identifier.to_string(),
+                        first: "name".to_string(),
+                        second: "capture".to_string(),
+                    },
+                    name.span,
+                )),
+                (Some(name), _) if !is_group_name(&name.node) => Err(invalid_value(
+                    identifier,
+                    "name",
+                    "a name made of letters, digits and `_`, not starting with a digit",
+                    name.span,
+                )),
+                (Some(name), _) => Ok((
+                    Function::NamedGroup {
+                        name: name.node,
+                        body,
+                    },
+                    None,
+                )),
+                (None, Some(false)) => Ok((Function::NonCapturingGroup(body), None)),
+                (None, _) => Ok((Function::Group(body), None)),
+            };
+        }
+        "either" | "one_of_patterns" => {
+            let branches = arguments.patterns_from(identifier, 2, span)?;
+
+            return Ok((Function::Either(branches), None));
+        }
+        "start" | "end" | "line_start" | "line_end" | "word_boundary" | "not_word_boundary" => {
+            let anchor = match identifier {
+                "start" => Function::Start,
+                "end" => Function::End,
+                "line_start" => Function::LineStart,
+                "line_end" => Function::LineEnd,
+                "word_boundary" => Function::WordBoundary,
+                _ => Function::NotWordBoundary,
+            };
+
+            return Ok((anchor, None));
+        }
+        "literal" => {
+            let text = match arguments.values(identifier, 1, span)?.remove(0) {
+                Spanned {
+                    node: Value::String(text),
+                    span,
+                } if text.is_empty() => return Err(Error::new(ErrorKind::EmptyLiteral, span)),
+                Spanned {
+                    node: Value::String(text),
+                    ..
+                } => text,
+                value => {
+                    return Err(invalid_value(identifier, "text", "a string", value.span));
+                }
+            };
+
+            return Ok((Function::Literal(text), None));
+        }
+        "same_as" => {
+            let reference = match arguments.values(identifier, 1, span)?.remove(0) {
+                Spanned {
+                    node: Value::Int(number),
+                    ..
+                } if number > 0 => Reference::Number(number),
+                Spanned {
+                    node: Value::String(name),
+                    ..
+                } if is_group_name(&name) => Reference::Name(name),
+                value => {
+                    return Err(invalid_value(
+                        identifier,
+                        "group",
+                        "a group number from 1 or a group name",
+                        value.span,
+                    ));
+                }
+            };
+
+            return Ok((Function::SameAs(reference), None));
+        }
+        "range" => {
+            let mut values = arguments.values(identifier, 2, span)?;
+            let end = single_char(identifier, "end", values.remove(1))?;
+            let start = single_char(identifier, "start", values.remove(0))?;
+
+            if start > end {
+                return Err(Error::new(ErrorKind::InvalidRange { start, end }, span));
+            }
+
+            return Ok((Function::Range(start, end), None));
+        }
+        "one_of" | "none_of" => {
+            let members = class_members(identifier, arguments, span)?;
+
+            return match identifier {
+                "one_of" => Ok((Function::OneOf(members), None)),
+                _ => Ok((Function::NoneOf(members), None)),
+            };
+        }
+        "not" => {
+            let pattern = arguments.patterns(identifier, 1, span)?.remove(0);
+            let negated = match pattern.as_slice() {
+                [node] if node.quantifier.is_some() => Some("a quantified function"),
+                [node] => negated_kind(&node.function),
+                _ => Some("a sequence of functions"),
+            };
+
+            if let Some(found) = negated {
+                let span = pattern[0].span.to(pattern[pattern.len() - 1].span);
+
+                return Err(Error::new(ErrorKind::CannotNegate { found }, span));
+            }
+
+            return Ok((Function::NoneOf(pattern), None));
+        }
+        "exact" => {
+            let body = arguments.patterns(identifier, 1, span)?.remove(0);
+
+            return Ok((Function::Exact(body), None));
+        }
+        "flags" => {
+            return Err(Error::new(ErrorKind::MisplacedFlags, span));
+        }
+        "ignore_case" => {
+            let body = arguments.patterns(identifier, 1, span)?.remove(0);
+
+            return Ok((Function::IgnoreCase(body), None));
+        }
+        "followed_by" | "not_followed_by" | "preceded_by" | "not_preceded_by" => {
+            let body = arguments.patterns(identifier, 1, span)?.remove(0);
+
+            return match identifier {
+                "followed_by" => Ok((Function::FollowedBy(body), None)),
+                "not_followed_by" => Ok((Function::NotFollowedBy(body), None)),
+                "preceded_by" => Ok((Function::PrecededBy(body), None)),
+                _ => Ok((Function::NotPrecededBy(body), None)),
+            };
+        }
+        _ => unreachable!("functions without a schema are rejected by the parser"),
+    }
+}
+
+/// Builds `repeat(x, times=4, sep=...)` or `separated_by(x, sep=..., min=1)`,
+/// which write `x` once and repeat only `sep x`, e.g. `x(?:\.x){3}`.
+fn build_repetition(
+    function: &str,
+    schema: &Schema,
+    arguments: &mut Arguments,
+    span: Span,
+) -> Result<Vec<Node>, Error> {
+    arguments.validate(function, schema)?;
+    arguments.values(function, 0, span)?;
+
+    let item = arguments.patterns(function, 1, span)?.remove(0);
+    let sep = arguments.pattern("sep");
+    let missing = |parameter: &str| {
+        return Error::new(
+            ErrorKind::MissingParameter {
+                function: function.to_string(),
+                parameter: parameter.to_string(),
+            },
+            span,
+        );
+    };
+
+    let (count, (min, max)) = match function {
+        "repeat" => match arguments.find("times").map(|param| &param.value) {
+            Some(Spanned {
+                node: Value::Int(times),
+                span,
+            }) => (("times", *span), (*times, Some(*times))),
+            Some(Spanned {
+                node: Value::Range(min, max),
+                span,
+            }) => (("times", *span), (*min, *max)),
+            _ => return Err(missing("times")),
+        },
+        _ => {
+            if sep.is_none() {
+                return Err(missing("sep"));
+            }
+
+            let min = arguments.int(function, "min")?.unwrap_or(1);
+            let max = arguments.int(function, "max")?;
+            let span = arguments
+                .find("max")
+                .or(arguments.find("min"))
+                .map(|param| param.value.span)
+                .unwrap_or(span);
+
+            (("max", span), (min, max))
+        }
+    };
+
+    match max {
+        Some(max) if max < min => {
+            return Err(Error::new(
+                ErrorKind::InvalidQuantifier { min, max },
+                count.1,
+            ));
+        }
+        Some(0) => {
+            return Err(invalid_value(function, count.0, "a count from 1", count.1));
+        }
+        _ => {}
+    }
+
+    // Anchors and lookarounds can't take a quantifier.
+    if item.iter().all(|node| is_zero_width(&node.function)) {
+        return Err(Error::new(
+            ErrorKind::ZeroWidthRepetition {
+                function: function.to_string(),
+            },
+            item[0].span.to(item[item.len() - 1].span),
+        ));
+    }
+
+    let Some(sep) = sep else {
+        let quantifier = Quantifier {
+            min,
+            max,
+            mode: Mode::Greedy,
+        };
+
+        return Ok(vec![quantify(item, quantifier, span)]);
+    };
+
+    // The item is written again after the separator, and a group would be
+    // numbered, or named, twice.
+    if let Some(group) = find_capture(&item) {
+        return Err(Error::new(
+            ErrorKind::CaptureInRepetition {
+                function: function.to_string(),
+            },
+            group,
+        ));
+    }
+
+    // Every item but the first comes after a separator.
+    let rest = Quantifier {
+        min: min.saturating_sub(1),
+        max: max.map(|max| max - 1),
+        mode: Mode::Greedy,
+    };
+    let mut tail: Vec<Node> = sep.into_iter().chain(item.iter().cloned()).collect();
+    let mut nodes = item;
+
+    match (rest.min, rest.max) {
+        (0, Some(0)) => {}
+        (1, Some(1)) => nodes.append(&mut tail),
+        _ => nodes.push(quantify(tail, rest, span)),
+    }
+
+    if min == 0 {
+        return Ok(vec![quantify(nodes, Quantifier::between(0, 1), span)]);
+    }
+
+    return Ok(nodes);
+}
+
+fn is_zero_width(function: &Function) -> bool {
+    return matches!(
+        function,
+        Function::Start
+            | Function::End
+            | Function::LineStart
+            | Function::LineEnd
+            | Function::WordBoundary
+            | Function::NotWordBoundary
+            | Function::FollowedBy(_)
+            | Function::NotFollowedBy(_)
+            | Function::PrecededBy(_)
+            | Function::NotPrecededBy(_)
+    );
+}
+
+/// Span of the first capturing group in `nodes`, at any depth.
+fn find_capture(nodes: &[Node]) -> Option<Span> {
+    return nodes.iter().find_map(|node| match &node.function {
+        Function::Group(_) | Function::NamedGroup { .. } => Some(node.span),
+        Function::NonCapturingGroup(body)
+        | Function::Exact(body)
+        | Function::FollowedBy(body)
+        | Function::NotFollowedBy(body)
+        | Function::PrecededBy(body)
+        | Function::NotPrecededBy(body)
+        | Function::IgnoreCase(body) => find_capture(body),
+        Function::Either(branches) => branches.iter().find_map(|branch| find_capture(branch)),
+        _ => None,
+    });
+}
+
+/// Applies `quantifier` to `nodes`, grouping them unless they are a single
+/// unquantified node.
+fn quantify(mut nodes: Vec<Node>, quantifier: Quantifier, span: Span) -> Node {
+    let mut node = match nodes.as_slice() {
+        [node] if node.quantifier.is_none() => nodes.remove(0),
+        _ => Node::new(Function::NonCapturingGroup(nodes), span),
+    };
+    node.quantifier = Some(quantifier);
+
+    return node;
+}
+
+/// A single member as is, several as a class matching any of them.
+fn any_of(mut members: Vec<Node>) -> Function {
+    if members.len() == 1 {
+        return members.remove(0).function;
+    }
+
+    return Function::OneOf(members);
+}
+
+/// Capturing groups met so far while checking the backreferences.
+#[derive(Default)]
+struct Groups {
+    opened: u32,
+    /// Number and name of the groups whose body is over.
+    closed: Vec<(u32, Option<String>)>,
+}
+
+/// Makes sure every `same_as` points to a capturing group closed before it,
+/// and that group names are unique once bindings are expanded.
+fn check_references(nodes: &[Node], groups: &mut Groups) -> Result<(), Error> {
+    for node in nodes {
+        match &node.function {
+            Function::Group(body) | Function::NamedGroup { body, .. } => {
+                groups.opened += 1;
+                let number = groups.opened;
+
+                check_references(body, groups)?;
+
+                let name = match &node.function {
+                    Function::NamedGroup { name, .. } => Some(name.clone()),
+                    _ => None,
+                };
+
+                if let Some(name) = &name {
+                    if groups
+                        .closed
+                        .iter()
+                        .any(|(_, closed)| closed.as_ref() == Some(name))
+                    {
+                        return Err(Error::new(
+                            ErrorKind::DuplicateGroupName(name.clone()),
+                            node.span,
+                        ));
+                    }
+                }
+
+                groups.closed.push((number, name));
+            }
+            Function::NonCapturingGroup(body)
+            | Function::Exact(body)
+            | Function::FollowedBy(body)
+            | Function::NotFollowedBy(body)
+            | Function::PrecededBy(body)
+            | Function::NotPrecededBy(body)
+            | Function::IgnoreCase(body) => check_references(body, groups)?,
+            Function::Either(branches) => {
+                for branch in branches {
+                    check_references(branch, groups)?;
+                }
+            }
+            Function::SameAs(reference) => {
+                let defined = groups.closed.iter().any(|(number, name)| match reference {
+                    Reference::Number(referenced) => number == referenced,
+                    Reference::Name(referenced) => name.as_ref() == Some(referenced),
+                });
+
+                if !defined {
+                    return Err(Error::new(
+                        ErrorKind::UndefinedGroup(reference.clone()),
+                        node.span,
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    return Ok(());
+}
+
+/// Parses an imported file, which only holds statements.
+fn parse_module<'a>(
+    text: String,
+    path: PathBuf,
+    imports: &'a RefCell<Imports<'a>>,
+) -> Result<Module, Error> {
+    let mut parser = Parser::new(tokens::tokenize(text)?, imports);
+    parser.path = Some(path);
+
+    parser.parse_statements()?;
+
+    if parser.peek().is_some() {
+        return Err(parser.unexpected("`let`, `def` or `import`"));
+    }
+
+    return Ok(Module {
+        bindings: parser.bindings,
+        definitions: parser.definitions,
+    });
+}
+
+/// Reports an error found in an imported file at the place of the pattern
+/// that led to it, as its span is meaningless in the pattern.
+fn in_module(error: Error, path: &str, span: Span) -> Error {
+    return Error::new(
+        ErrorKind::InModule {
+            path: path.to_string(),
+            line: error.span.line,
+            column: error.span.column,
+            message: error.kind.to_string(),
+        },
+        span,
+    );
+}
+
+/// Points `nodes` and everything in them at `span`, for nodes parsed from
+/// another file.
+fn respan(nodes: &mut [Node], span: Span) {
+    for node in nodes {
+        node.span = span;
+
+        match &mut node.function {
+            Function::Group(body)
+            | Function::NamedGroup { body, .. }
+            | Function::NonCapturingGroup(body)
+            | Function::Exact(body)
+            | Function::FollowedBy(body)
+            | Function::NotFollowedBy(body)
+            | Function::PrecededBy(body)
+            | Function::NotPrecededBy(body)
+            | Function::IgnoreCase(body)
+            | Function::OneOf(body)
+            | Function::NoneOf(body) => respan(body, span),
+            Function::Either(branches) => {
+                for branch in branches {
+                    respan(branch, span);
+                }
+            }
+            Function::Intersection(left, right) | Function::Difference(left, right) => {
+                respan(std::slice::from_mut(left.as_mut()), span);
+                respan(std::slice::from_mut(right.as_mut()), span);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Parses a pattern that can't import anything.
+pub fn parse(tokens: Vec<Spanned<Token>>) -> Result<Pattern, Error> {
+    return parse_with_loader(tokens, &NoLoader);
+}
+
+/// Same as `parse`, reading the files given to `import` with `loader`.
+pub fn parse_with_loader(
+    tokens: Vec<Spanned<Token>>,
+    loader: &dyn Loader,
+) -> Result<Pattern, Error> {
+    let imports = RefCell::new(Imports {
+        loader,
+        cache: HashMap::new(),
+        stack: vec![],
+    });
+    let pattern = Parser::new(tokens, &imports).parse_pattern()?;
+
+    check_references(&pattern.nodes, &mut Groups::default())?;
+
+    return Ok(pattern);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokens;
+
+    fn parse_input(input: &str) -> Result<Vec<Node>, ErrorKind> {
+        return parse_flags(input).map(|pattern| pattern.nodes);
+    }
+
+    fn parse_flags(input: &str) -> Result<Pattern, ErrorKind> {
+        return parse(tokens::tokenize(input.to_string()).unwrap()).map_err(|error| error.kind);
+    }
+
+    fn nodes(functions: Vec<Function>) -> Vec<Node> {
+        return functions.into_iter().map(Node::from).collect();
+    }
+
+    fn quantified(function: Function, quantifier: Quantifier) -> Node {
+        let mut node = Node::from(function);
+        node.quantifier = Some(quantifier);
+
+        return node;
+    }
+
+    fn whitespace() -> Function {
+        return Function::Whitespace {
+            kind: WhitespaceKind::Any,
+        };
+    }
+
+    fn letter(casing: Casing) -> Function {
+        return Function::Letter { casing };
+    }
+
+    #[test]
+    fn test_grouped_tokens() {
+        let input = String::from(
+            "group(letters(case=\"upper\") | glob(rest=True)) | whitespace | group(numbers)",
+        );
+
+        assert_eq!(
+            parse_input(&input),
+            Ok(vec![
+                Function::Group(vec![
+                    quantified(letter(Casing::Upcase), Quantifier::at_least(1)),
+                    quantified(Function::Glob, Quantifier::at_least(0)),
+                ])
+                .into(),
+                whitespace().into(),
+                Function::Group(vec![quantified(Function::Number, Quantifier::at_least(1))]).into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_basic_tokenize() {
+        let input = String::from(
+            "letter(case=\"upper\") | letter(case=\"lower\") | glob(rest=True) | glob(rest=False) | whitespace | number",
+        );
+
+        assert_eq!(
+            parse_input(&input),
+            Ok(vec![
+                letter(Casing::Upcase).into(),
+                letter(Casing::Downcase).into(),
+                quantified(Function::Glob, Quantifier::at_least(0)),
+                Function::Glob.into(),
+                whitespace().into(),
+                Function::Number.into(),
+            ])
+        );
+
+        let input =
+            String::from("letters(case=\"upper\") | glob(rest=True) | whitespace | numbers");
+
+        assert_eq!(
+            parse_input(&input),
+            Ok(vec![
+                quantified(letter(Casing::Upcase), Quantifier::at_least(1)),
+                quantified(Function::Glob, Quantifier::at_least(0)),
+                whitespace().into(),
+                quantified(Function::Number, Quantifier::at_least(1)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_select_parameters() {
+        let input = String::from(
+            "letter(select=3, case=\"upper\") | letters(case=\"lower\") | glob(rest=True) | glob(rest=False) | whitespace | number(select=99)",
+        );
+
+        assert_eq!(
+            parse_input(&input),
+            Ok(vec![
+                quantified(letter(Casing::Upcase), Quantifier::exactly(3)),
+                quantified(letter(Casing::Downcase), Quantifier::at_least(1)),
+                quantified(Function::Glob, Quantifier::at_least(0)),
+                Function::Glob.into(),
+                whitespace().into(),
+                quantified(Function::Number, Quantifier::exactly(99)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_quantifier_parameters() {
+        assert_eq!(
+            parse_input("letters(select=2..5) | numbers(min=3) | whitespace(optional=True)"),
+            Ok(vec![
+                quantified(letter(Casing::Any), Quantifier::between(2, 5)),
+                quantified(Function::Number, Quantifier::at_least(3)),
+                quantified(whitespace(), Quantifier::between(0, 1)),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("group(letter, many=True) | either(letter, number, max=2)"),
+            Ok(vec![
+                quantified(
+                    Function::Group(nodes(vec![letter(Casing::Any)])),
+                    Quantifier::at_least(1)
+                ),
+                quantified(
+                    Function::Either(vec![
+                        nodes(vec![letter(Casing::Any)]),
+                        nodes(vec![Function::Number])
+                    ]),
+                    Quantifier::between(0, 2)
+                ),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("number(select=3, min=2)"),
+            Err(ErrorKind::ConflictingParameters {
+                function: "number".to_string(),
+                first: "select".to_string(),
+                second: "min".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("glob(rest=True, many=True)"),
+            Err(ErrorKind::ConflictingParameters {
+                function: "glob".to_string(),
+                first: "rest".to_string(),
+                second: "many".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("number(select=5..2)"),
+            Err(ErrorKind::InvalidQuantifier { min: 5, max: 2 })
+        );
+
+        assert_eq!(
+            parse_input("number(select=True)"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "number".to_string(),
+                parameter: "select".to_string(),
+                expected: "an integer or a range like `2..5`".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_repetition() {
+        let octet = || quantified(Function::Number, Quantifier::between(1, 3));
+        let dot = || Node::from(Function::Literal(".".to_string()));
+
+        assert_eq!(
+            parse_input("repeat(number(select=1..3), times=4, sep=literal(\".\"))"),
+            Ok(vec![
+                octet(),
+                quantified(
+                    Function::NonCapturingGroup(vec![dot(), octet()]),
+                    Quantifier::exactly(3)
+                ),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("repeat(number, times=2, sep=literal(\".\"))"),
+            Ok(vec![
+                Function::Number.into(),
+                dot(),
+                Function::Number.into()
+            ])
+        );
+
+        assert_eq!(
+            parse_input("separated_by(number, sep=literal(\".\"), min=0)"),
+            Ok(vec![quantified(
+                Function::NonCapturingGroup(vec![
+                    Function::Number.into(),
+                    quantified(
+                        Function::NonCapturingGroup(vec![dot(), Function::Number.into()]),
+                        Quantifier::at_least(0)
+                    ),
+                ]),
+                Quantifier::between(0, 1)
+            )])
+        );
+
+        assert_eq!(
+            parse_input("separated_by(numbers)"),
+            Err(ErrorKind::MissingParameter {
+                function: "separated_by".to_string(),
+                parameter: "sep".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("repeat(number, times=0)"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "repeat".to_string(),
+                parameter: "times".to_string(),
+                expected: "a count from 1".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("repeat(number, times=3..2)"),
+            Err(ErrorKind::InvalidQuantifier { min: 3, max: 2 })
+        );
+
+        assert_eq!(
+            parse_input("repeat(group(numbers), times=2)"),
+            Ok(vec![quantified(
+                Function::Group(vec![quantified(Function::Number, Quantifier::at_least(1))]),
+                Quantifier::exactly(2)
+            )])
+        );
+
+        for input in [
+            "repeat(group(number, name=\"a\"), times=3, sep=literal(\".\"))",
+            "separated_by(either(group(number), letter), sep=literal(\",\"))",
+        ] {
+            assert_eq!(
+                parse_input(input),
+                Err(ErrorKind::CaptureInRepetition {
+                    function: input[..input.find('(').unwrap()].to_string(),
+                })
+            );
+        }
+
+        for input in [
+            "repeat(start, times=2)",
+            "repeat(word_boundary, times=2..)",
+            "separated_by(followed_by(letter) | line_end, sep=literal(\",\"))",
+        ] {
+            assert_eq!(
+                parse_input(input),
+                Err(ErrorKind::ZeroWidthRepetition {
+                    function: input[..input.find('(').unwrap()].to_string(),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_parameter_schema() {
+        let script = |name: &str| Function::Property(Property::Script(name.to_string()));
+        let kind = |kind| Function::Whitespace { kind };
+
+        assert_eq!(
+            parse_input("letter(script=[Latin, \"Greek\"], many=True) | letters(case=upper)"),
+            Ok(vec![
+                quantified(
+                    Function::OneOf(nodes(vec![script("Latin"), script("Greek")])),
+                    Quantifier::at_least(1)
+                ),
+                quantified(letter(Casing::Upcase), Quantifier::at_least(1)),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("whitespace(kind=[space, tab]) | whitespace(kind=[newline])"),
+            Ok(nodes(vec![
+                Function::OneOf(nodes(vec![
+                    kind(WhitespaceKind::Space),
+                    kind(WhitespaceKind::Tab)
+                ])),
+                kind(WhitespaceKind::Newline),
+            ]))
+        );
+
+        assert_eq!(
+            parse_input("number(select=3, select=4)"),
+            Err(ErrorKind::RepeatedParameter {
+                function: "number".to_string(),
+                parameter: "select".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("letter(case=[upper])"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "letter".to_string(),
+                parameter: "case".to_string(),
+                expected: "`upper`, `lower` or `any`".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("letter(script=[])"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "letter".to_string(),
+                parameter: "script".to_string(),
+                expected: "a name like `Greek` or a string, or a list of them".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("number(lazy=True, possessive=False)"),
+            Err(ErrorKind::ConflictingParameters {
+                function: "number".to_string(),
+                first: "lazy".to_string(),
+                second: "possessive".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("start(many=True)"),
+            Err(ErrorKind::UnknownParameter {
+                function: "start".to_string(),
+                parameter: "many".to_string(),
+                suggestion: None,
+            })
+        );
+
+        assert_eq!(
+            parse_input("flags(multiline=1) | letter"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "flags".to_string(),
+                parameter: "multiline".to_string(),
+                expected: "`True` or `False`".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(
+            parse_input("leters(upcase=True)"),
+            Err(ErrorKind::UnknownFunction {
+                name: "leters".to_string(),
+                suggestion: Some("letters".to_string()),
+            })
+        );
+
+        assert_eq!(
+            parse_input("letter(upcase=True)"),
+            Err(ErrorKind::UnknownParameter {
+                function: "letter".to_string(),
+                parameter: "upcase".to_string(),
+                suggestion: Some("case".to_string()),
+            })
+        );
+
+        assert_eq!(
+            parse_input("let digits = numbers; def pair(x) = x | x; digit | pairs(letter)"),
+            Err(ErrorKind::UnknownFunction {
+                name: "digit".to_string(),
+                suggestion: Some("digits".to_string()),
+            })
+        );
+
+        assert_eq!(
+            parse_input("def pair(x) = x | x; pairs(letter)"),
+            Err(ErrorKind::UnknownFunction {
+                name: "pairs".to_string(),
+                suggestion: Some("pair".to_string()),
+            })
+        );
+
+        assert_eq!(
+            parse_input("bogus"),
+            Err(ErrorKind::UnknownFunction {
+                name: "bogus".to_string(),
+                suggestion: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_builtin_functions_have_a_schema() {
+        for function in BUILTIN_FUNCTIONS {
+            assert!(schema(function).is_some(), "{function} has no schema");
+        }
+    }
+
+    #[test]
+    fn test_invalid_functions() {
+        assert_eq!(
+            parse_input("letter | leter"),
+            Err(ErrorKind::UnknownFunction {
+                name: "leter".to_string(),
+                suggestion: Some("letter".to_string()),
+            })
+        );
+
+        assert_eq!(
+            parse_input("glob | whitespace"),
+            Err(ErrorKind::MissingParameter {
+                function: "glob".to_string(),
+                parameter: "rest".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("number(selct=3)"),
+            Err(ErrorKind::UnknownParameter {
+                function: "number".to_string(),
+                parameter: "selct".to_string(),
+                suggestion: Some("select".to_string()),
+            })
+        );
+
+        assert_eq!(
+            parse_input("letter(case=\"title\")"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "letter".to_string(),
+                parameter: "case".to_string(),
+                expected: "`upper`, `lower` or `any`".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("group(letter | number"),
+            Err(ErrorKind::UnclosedParen)
+        );
+        assert_eq!(
+            parse_input("group(letter) | number)"),
+            Err(ErrorKind::UnexpectedRightParen)
+        );
+    }
+
+    #[test]
+    fn test_function_without_parameters() {
+        let input = String::from("letter | number(select=3)");
+
+        assert_eq!(
+            parse_input(&input),
+            Ok(vec![
+                letter(Casing::Any).into(),
+                quantified(Function::Number, Quantifier::exactly(3)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_function_spans() {
+        let functions = parse(
+            tokens::tokenize(String::from("letter(case=\"upper\") | group(numbers)")).unwrap(),
+        )
+        .unwrap()
+        .nodes;
+
+        assert_eq!(functions[0].span, Span::new(0, 20, 1, 1));
+        assert_eq!(functions[1].span, Span::new(23, 37, 1, 24));
+
+        if let Function::Group(group) = &functions[1].function {
+            assert_eq!(group[0].span, Span::new(29, 36, 1, 30));
+        }
+
+        let error = parse(tokens::tokenize(String::from("letter | leter")).unwrap()).unwrap_err();
+
+        assert_eq!(error.span, Span::new(9, 14, 1, 10));
+    }
+
+    #[test]
+    fn test_nested_groups() {
+        let expected = Ok(vec![
+            Function::Group(vec![
+                Function::Group(vec![
+                    quantified(letter(Casing::Upcase), Quantifier::exactly(2)),
+                    Function::Group(vec![quantified(Function::Number, Quantifier::at_least(1))])
+                        .into(),
+                ])
+                .into(),
+                quantified(Function::Number, Quantifier::exactly(3)),
+            ])
+            .into(),
+            whitespace().into(),
+        ]);
+
+        assert_eq!(
+            parse_input(
+                "group(group(letter(case=\"upper\", select=2) | group(numbers)) | number(select=3)) | whitespace"
+            ),
+            expected
+        );
+
+        assert_eq!(
+            parse_input(
+                "group (  group(letter( case = \"upper\",select=2 )|group( numbers ) ) |number(select = 3))|whitespace"
+            ),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_unexpected_tokens() {
+        assert_eq!(
+            parse_input("letter |"),
+            Err(ErrorKind::UnexpectedToken(
+                "end of input, expected a function".to_string()
+            ))
+        );
+
+        assert_eq!(
+            parse_input("letter number"),
+            Err(ErrorKind::UnexpectedToken(
+                "function `number`, expected `|`".to_string()
+            ))
+        );
+
+        assert_eq!(
+            parse_input("group(letter, number)"),
+            Err(ErrorKind::PatternCount {
+                function: "group".to_string(),
+                expected: 1,
+                found: 2,
+            })
+        );
+
+        assert_eq!(
+            parse_input("letter(number)"),
+            Err(ErrorKind::PatternCount {
+                function: "letter".to_string(),
+                expected: 0,
+                found: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_either() {
+        let expected = Ok(nodes(vec![Function::Either(vec![
+            vec![
+                quantified(letter(Casing::Any), Quantifier::at_least(1)),
+                whitespace().into(),
+            ],
+            vec![quantified(Function::Number, Quantifier::at_least(1))],
+            nodes(vec![Function::Group(nodes(vec![Function::Either(vec![
+                nodes(vec![Function::Number]),
+                nodes(vec![whitespace()]),
+            ])]))]),
+        ])]));
+
+        assert_eq!(
+            parse_input("either(letters | whitespace, numbers, group(either(number, whitespace)))"),
+            expected
+        );
+
+        assert_eq!(
+            parse_input(
+                "one_of_patterns(letters | whitespace, numbers, group(one_of_patterns(number, whitespace)))"
+            ),
+            expected
+        );
+
+        assert_eq!(
+            parse_input("either(letters)"),
+            Err(ErrorKind::NotEnoughPatterns {
+                function: "either".to_string(),
+                minimum: 2,
+                found: 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_quantifier_modes() {
+        let lazy = Quantifier {
+            mode: Mode::Lazy,
+            ..Quantifier::at_least(0)
+        };
+
+        assert_eq!(
+            parse_input("glob(rest=True, lazy=True) | glob(rest=True, greedy=False)"),
+            Ok(vec![
+                quantified(Function::Glob, lazy),
+                quantified(Function::Glob, lazy),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("numbers(possessive=True)"),
+            Ok(vec![quantified(
+                Function::Number,
+                Quantifier {
+                    mode: Mode::Possessive,
+                    ..Quantifier::at_least(1)
+                }
+            )])
+        );
+
+        assert_eq!(
+            parse_input("number(lazy=True)"),
+            Err(ErrorKind::ModeWithoutQuantifier {
+                function: "number".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("numbers(lazy=True, possessive=True)"),
+            Err(ErrorKind::ConflictingParameters {
+                function: "numbers".to_string(),
+                first: "lazy".to_string(),
+                second: "possessive".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_named_groups() {
+        assert_eq!(
+            parse_input("group(number(select=4), name=\"year\") | group(letters, capture=False)"),
+            Ok(vec![
+                Function::NamedGroup {
+                    name: "year".to_string(),
+                    body: vec![quantified(Function::Number, Quantifier::exactly(4))],
+                }
+                .into(),
+                Function::NonCapturingGroup(vec![quantified(
+                    letter(Casing::Any),
+                    Quantifier::at_least(1)
+                )])
+                .into(),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("group(letter, name=\"a\") | group(group(number, name=\"a\"))"),
+            Err(ErrorKind::DuplicateGroupName("a".to_string()))
+        );
+
+        assert_eq!(
+            parse_input("group(letter, name=\"a\", capture=False)"),
+            Err(ErrorKind::ConflictingParameters {
+                function: "group".to_string(),
+                first: "name".to_string(),
+                second: "capture".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("group(letter, name=\"2nd\")"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "group".to_string(),
+                parameter: "name".to_string(),
+                expected: "a name made of letters, digits and `_`, not starting with a digit"
+                    .to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_anchors() {
+        assert_eq!(
+            parse_input("start | line_start | word_boundary | not_word_boundary | line_end | end"),
+            Ok(nodes(vec![
+                Function::Start,
+                Function::LineStart,
+                Function::WordBoundary,
+                Function::NotWordBoundary,
+                Function::LineEnd,
+                Function::End,
+            ]))
+        );
+
+        assert_eq!(
+            parse_input("exact(letters | numbers)"),
+            Ok(nodes(vec![Function::Exact(vec![
+                quantified(letter(Casing::Any), Quantifier::at_least(1)),
+                quantified(Function::Number, Quantifier::at_least(1)),
+            ])]))
+        );
+
+        assert_eq!(
+            parse_input("start(many=True)"),
+            Err(ErrorKind::UnknownParameter {
+                function: "start".to_string(),
+                parameter: "many".to_string(),
+                suggestion: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(
+            parse_input("literal(\"v1.2\") | literal(\"(foo)\", optional=True)"),
+            Ok(vec![
+                Function::Literal("v1.2".to_string()).into(),
+                quantified(
+                    Function::Literal("(foo)".to_string()),
+                    Quantifier::between(0, 1)
+                ),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("literal()"),
+            Err(ErrorKind::ValueCount {
+                function: "literal".to_string(),
+                expected: 1,
+                found: 0,
+            })
+        );
+
+        assert_eq!(
+            parse_input("letter(\"a\")"),
+            Err(ErrorKind::ValueCount {
+                function: "letter".to_string(),
+                expected: 0,
+                found: 1,
+            })
+        );
+
+        assert_eq!(parse_input("literal(\"\")"), Err(ErrorKind::EmptyLiteral));
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert_eq!(
+            parse_input(
+                "one_of(\"-_.\", range(\"a\", \"f\"), number) | none_of(\"\\\"\", many=True)"
+            ),
+            Ok(vec![
+                Function::OneOf(vec![
+                    Function::Literal("-_.".to_string()).into(),
+                    Function::Range('a', 'f').into(),
+                    Function::Number.into(),
+                ])
+                .into(),
+                quantified(
+                    Function::NoneOf(nodes(vec![Function::Literal("\"".to_string())])),
+                    Quantifier::at_least(1)
+                ),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("range(\"f\", \"a\")"),
+            Err(ErrorKind::InvalidRange {
+                start: 'f',
+                end: 'a'
+            })
+        );
+
+        assert_eq!(
+            parse_input("range(\"a\", \"fg\")"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "range".to_string(),
+                parameter: "end".to_string(),
+                expected: "a single character".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("one_of(\"ab\", group(letter))"),
+            Err(ErrorKind::NotACharacterClass {
+                function: "one_of".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("one_of(letters)"),
+            Err(ErrorKind::NotACharacterClass {
+                function: "one_of".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("one_of(literal(\"ab\"), number)"),
+            Err(ErrorKind::NotACharacterClass {
+                function: "one_of".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("none_of(literal(\"é\"))"),
+            Ok(nodes(vec![Function::NoneOf(nodes(vec![
+                Function::Literal("é".to_string())
+            ]))]))
+        );
+
+        assert_eq!(
+            parse_input("none_of()"),
+            Err(ErrorKind::EmptyClass {
+                function: "none_of".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let letter = || -> Box<Node> { Box::new(letter(Casing::Any).into()) };
+        let vowels = || -> Box<Node> {
+            Box::new(Function::OneOf(nodes(vec![Function::Literal("aeiou".to_string())])).into())
+        };
+
+        assert_eq!(
+            parse_input("letter - one_of(\"aeiou\") | number"),
+            Ok(vec![
+                Function::Difference(letter(), vowels()).into(),
+                Function::Number.into(),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("letter + number & one_of(\"aeiou\")"),
+            Ok(vec![Function::OneOf(vec![
+                *letter(),
+                Function::Intersection(Box::new(Function::Number.into()), vowels()).into(),
+            ])
+            .into()])
+        );
+
+        assert_eq!(
+            parse_input("(letter + number) & one_of(\"aeiou\")"),
+            Ok(vec![Function::Intersection(
+                Box::new(Function::OneOf(vec![*letter(), Function::Number.into()]).into()),
+                vowels(),
+            )
+            .into()])
+        );
+
+        assert_eq!(
+            parse_input("letter - group(number)"),
+            Err(ErrorKind::NotASetOperand("`-`".to_string()))
+        );
+
+        assert_eq!(
+            parse_input("letters & number"),
+            Err(ErrorKind::NotASetOperand("`&`".to_string()))
+        );
+
+        assert_eq!(
+            parse_input("literal(\"ab\") + number"),
+            Err(ErrorKind::NotASetOperand("`+`".to_string()))
+        );
+
+        assert_eq!(
+            parse_input("letter - literal(\"abc\")"),
+            Err(ErrorKind::NotASetOperand("`-`".to_string()))
+        );
+
+        assert_eq!(
+            parse_input("letter - literal(\"a\")"),
+            Ok(vec![Function::Difference(
+                letter(),
+                Box::new(Function::Literal("a".to_string()).into())
+            )
+            .into()])
+        );
+
+        assert_eq!(
+            parse_input("(letter + number"),
+            Err(ErrorKind::UnclosedParen)
+        );
+    }
+
+    #[test]
+    fn test_not() {
+        assert_eq!(
+            parse_input("not(number) | not(whitespace, many=True)"),
+            Ok(vec![
+                Function::NoneOf(nodes(vec![Function::Number])).into(),
+                quantified(
+                    Function::NoneOf(nodes(vec![whitespace()])),
+                    Quantifier::at_least(1)
+                ),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("letter & not(one_of(\"aeiou\"))"),
+            Ok(vec![Function::Intersection(
+                Box::new(letter(Casing::Any).into()),
+                Box::new(
+                    Function::NoneOf(nodes(vec![Function::OneOf(nodes(vec![
+                        Function::Literal("aeiou".to_string())
+                    ]))]))
+                    .into()
+                ),
+            )
+            .into()])
+        );
+
+        assert_eq!(
+            parse_input("not(group(number))"),
+            Err(ErrorKind::CannotNegate { found: "a group" })
+        );
+        assert_eq!(
+            parse_input("not(literal(\"ab\"))"),
+            Err(ErrorKind::CannotNegate {
+                found: "a `literal`, use `none_of` to exclude characters"
+            })
+        );
+        assert_eq!(
+            parse_input("not(letter | number)"),
+            Err(ErrorKind::CannotNegate {
+                found: "a sequence of functions"
+            })
+        );
+        assert_eq!(
+            parse_input("not(numbers)"),
+            Err(ErrorKind::CannotNegate {
+                found: "a quantified function"
+            })
+        );
+    }
+
+    #[test]
+    fn test_lookarounds() {
+        assert_eq!(
+            parse_input("preceded_by(literal(\"$\")) | numbers | not_followed_by(letter)"),
+            Ok(vec![
+                Function::PrecededBy(nodes(vec![Function::Literal("$".to_string())])).into(),
+                quantified(Function::Number, Quantifier::at_least(1)),
+                Function::NotFollowedBy(nodes(vec![letter(Casing::Any)])).into(),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("followed_by(letter, many=True)"),
+            Err(ErrorKind::UnknownParameter {
+                function: "followed_by".to_string(),
+                parameter: "many".to_string(),
+                suggestion: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_backreferences() {
+        assert_eq!(
+            parse_input("group(one_of(\"'\\\"\"), name=\"quote\") | letters | same_as(\"quote\")"),
+            Ok(vec![
+                Function::NamedGroup {
+                    name: "quote".to_string(),
+                    body: nodes(vec![Function::OneOf(nodes(vec![Function::Literal(
+                        "'\"".to_string()
+                    )]))]),
+                }
+                .into(),
+                quantified(letter(Casing::Any), Quantifier::at_least(1)),
+                Function::SameAs(Reference::Name("quote".to_string())).into(),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("group(group(letter) | number) | same_as(2, many=True)"),
+            Ok(vec![
+                Function::Group(vec![
+                    Function::Group(nodes(vec![letter(Casing::Any)])).into(),
+                    Function::Number.into(),
+                ])
+                .into(),
+                quantified(
+                    Function::SameAs(Reference::Number(2)),
+                    Quantifier::at_least(1)
+                ),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("same_as(1) | group(letter)"),
+            Err(ErrorKind::UndefinedGroup(Reference::Number(1)))
+        );
+        assert_eq!(
+            parse_input("group(letter | same_as(1))"),
+            Err(ErrorKind::UndefinedGroup(Reference::Number(1)))
+        );
+        assert_eq!(
+            parse_input("group(letter, capture=False) | same_as(1)"),
+            Err(ErrorKind::UndefinedGroup(Reference::Number(1)))
+        );
+        assert_eq!(
+            parse_input("group(letter, name=\"a\") | same_as(\"b\")"),
+            Err(ErrorKind::UndefinedGroup(Reference::Name("b".to_string())))
+        );
+        assert_eq!(
+            parse_input("group(letter) | same_as(0)"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "same_as".to_string(),
+                parameter: "group".to_string(),
+                expected: "a group number from 1 or a group name".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_unicode_classes() {
+        assert_eq!(
+            parse_input("letters(unicode=True, case=\"upper\") | number(unicode=True) | letter(script=\"Cyrillic\")"),
+            Ok(vec![
+                quantified(
+                    Function::Property(Property::Letter(Casing::Upcase)),
+                    Quantifier::at_least(1)
+                ),
+                Function::Property(Property::Number).into(),
+                Function::Property(Property::Script("Cyrillic".to_string())).into(),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("letter(unicode=False) | number(unicode=False)"),
+            Ok(nodes(vec![letter(Casing::Any), Function::Number]))
+        );
+
+        assert_eq!(
+            parse_input("letter(script=\"Greek\", case=\"upper\")"),
+            Err(ErrorKind::ConflictingParameters {
+                function: "letter".to_string(),
+                first: "script".to_string(),
+                second: "case".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("letter(script=\"Old Italic\")"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "letter".to_string(),
+                parameter: "script".to_string(),
+                expected: "a script name like `Cyrillic`".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        assert_eq!(
+            parse_input("ignore_case(literal(\"id\"), many=True) | letter(case=\"any\")"),
+            Ok(vec![
+                quantified(
+                    Function::IgnoreCase(nodes(vec![Function::Literal("id".to_string())])),
+                    Quantifier::at_least(1)
+                ),
+                letter(Casing::Any).into(),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("ignore_case()"),
+            Err(ErrorKind::PatternCount {
+                function: "ignore_case".to_string(),
+                expected: 1,
+                found: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        let pattern = parse_flags("flags(multiline=True, ignore_case=True) | letters").unwrap();
+
+        assert_eq!(
+            pattern.flags.node,
+            Flags {
+                multiline: true,
+                ignore_case: true,
+                ..Flags::default()
+            }
+        );
+        assert_eq!(pattern.flags.span, Span::new(0, 39, 1, 1));
+        assert_eq!(
+            pattern.nodes,
+            vec![quantified(letter(Casing::Any), Quantifier::at_least(1))]
+        );
+
+        assert_eq!(
+            parse_flags("flags(dotall=True)").map(|pattern| pattern.flags.node.dotall),
+            Ok(true)
+        );
+        assert_eq!(
+            parse_flags("letters | flags(dotall=True)"),
+            Err(ErrorKind::MisplacedFlags)
+        );
+        assert_eq!(
+            parse_flags("flags(global=True) | letters"),
+            Err(ErrorKind::UnknownParameter {
+                function: "flags".to_string(),
+                parameter: "global".to_string(),
+                suggestion: None,
+            })
+        );
+        assert_eq!(
+            parse_flags("flags(dotall=True) letters"),
+            Err(ErrorKind::UnexpectedToken(
+                "function `letters`, expected `|`".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_predefined_classes() {
+        assert_eq!(
+            parse_input(
+                "words | alnum | hex_digit(select=2) | punctuation | any_char(optional=True)"
+            ),
+            Ok(vec![
+                quantified(Function::Word, Quantifier::at_least(1)),
+                Function::Alnum.into(),
+                quantified(Function::HexDigit, Quantifier::exactly(2)),
+                Function::Punctuation.into(),
+                quantified(Function::AnyChar, Quantifier::between(0, 1)),
+            ])
+        );
+
+        let kind = |kind| Function::Whitespace { kind };
+
+        assert_eq!(
+            parse_input(
+                "whitespaces(kind=\"space\") | tab | newline(many=True) | whitespace(kind=\"any\")"
+            ),
+            Ok(vec![
+                quantified(kind(WhitespaceKind::Space), Quantifier::at_least(1)),
+                kind(WhitespaceKind::Tab).into(),
+                quantified(kind(WhitespaceKind::Newline), Quantifier::at_least(1)),
+                whitespace().into(),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("whitespace(kind=\"nbsp\")"),
+            Err(ErrorKind::InvalidParameterValue {
+                function: "whitespace".to_string(),
+                parameter: "kind".to_string(),
+                expected: "`space`, `tab`, `newline` or `any`, or a list of them".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_bindings() {
+        assert_eq!(
+            parse_input("let year = number(select=4); let sep = literal(\"-\"); year | sep | year"),
+            Ok(vec![
+                quantified(Function::Number, Quantifier::exactly(4)),
+                Function::Literal("-".to_string()).into(),
+                quantified(Function::Number, Quantifier::exactly(4)),
+            ])
+        );
+
+        assert_eq!(
+            parse_input("let letter = number; let pair = letter | letter; pair | word"),
+            parse_input("number | number | word")
+        );
+
+        assert_eq!(
+            parse_input("let vowel = one_of(\"aeiou\"); letter - vowel"),
+            parse_input("letter - one_of(\"aeiou\")")
+        );
+
+        assert_eq!(
+            parse_input("let pair = number | number; letter + pair"),
+            Err(ErrorKind::NotASetOperand("`+`".to_string()))
+        );
+
+        assert_eq!(
+            parse_input("let year = number; year(select=4)"),
+            Err(ErrorKind::BindingWithArguments("year".to_string()))
+        );
+
+        assert_eq!(
+            parse_input("let year = number year"),
+            Err(ErrorKind::UnexpectedToken(
+                "function `year`, expected `|` or `;`".to_string()
+            ))
+        );
+
+        assert_eq!(
+            parse_input("let year = group(number, name=\"year\"); year | year"),
+            Err(ErrorKind::DuplicateGroupName("year".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_definitions() {
+        assert_eq!(
+            parse_input("def quoted(x) = literal(\"'\") | x | literal(\"'\"); quoted(letters)"),
+            parse_input("literal(\"'\") | letters | literal(\"'\")")
+        );
+
+        assert_eq!(
+            parse_input("def pair(a, b) = a | b; def twice(x) = pair(x, x); twice(number)"),
+            parse_input("number | number")
+        );
+
+        assert_eq!(
+            parse_input("let x = letter; def f(y) = x | y; let x = word; f(x)"),
+            parse_input("letter | word")
+        );
+
+        assert_eq!(
+            parse_input("def f(x) = x; f(letter, number)"),
+            Err(ErrorKind::PatternCount {
+                function: "f".to_string(),
+                expected: 1,
+                found: 2,
+            })
+        );
+
+        assert_eq!(
+            parse_input("def f(x, x) = x; f(letter)"),
+            Err(ErrorKind::DuplicateParameter {
+                function: "f".to_string(),
+                parameter: "x".to_string(),
+            })
+        );
+
+        assert_eq!(
+            parse_input("def f(x) = g(x); def g(x) = f(x); f(letter)"),
+            Err(ErrorKind::RecursiveFunction("f".to_string()))
+        );
+
+        let tokens = tokens::tokenize("def f(x) = x | bogus; f(letter)".to_string()).unwrap();
+        let error = parse(tokens).unwrap_err();
+
+        assert_eq!(
+            error.kind,
+            ErrorKind::UnknownFunction {
+                name: "bogus".to_string(),
+                suggestion: None,
+            }
+        );
+        assert_eq!((error.span.start, error.span.end), (15, 20));
+        assert_eq!(
+            error
+                .note
+                .map(|note| (note.node, note.span.start, note.span.end)),
+            Some((String::from("in this call to `f`"), 22, 31))
+        );
+    }
+
+    /// Memory loader counting how many times a file is read.
+    #[derive(Default)]
+    struct CountingLoader {
+        files: crate::loader::MemoryLoader,
+        loads: std::cell::Cell<usize>,
+    }
+
+    impl Loader for CountingLoader {
+        fn load(&self, path: &Path) -> std::io::Result<String> {
+            self.loads.set(self.loads.get() + 1);
+
+            return self.files.load(path);
+        }
+    }
+
+    fn parse_loaded(input: &str, loader: &dyn Loader) -> Result<Vec<Node>, ErrorKind> {
+        return parse_with_loader(tokens::tokenize(input.to_string()).unwrap(), loader)
+            .map(|pattern| pattern.nodes)
+            .map_err(|error| error.kind);
+    }
+
+    #[test]
+    fn test_imports() {
+        let mut loader = CountingLoader::default();
+        loader.files.insert(
+            "lib/common.rgx",
+            "import \"./net.rgx\" as net; let port = number(select=1..5); \
+             def address(x) = net.ip | literal(\":\") | x;",
+        );
+        loader.files.insert(
+            "lib/net.rgx",
+            "def octet() = number(select=1..3); \
+             let ip = octet | literal(\".\") | octet | literal(\".\") | octet | literal(\".\") | octet;",
+        );
+        loader
+            .files
+            .insert("main.rgx", "import \"lib/net.rgx\" as net;");
+
+        assert_eq!(
+            parse_loaded(
+                "import \"lib/common.rgx\" as common; import \"lib/../main.rgx\" as main; \
+                 common.address(common.port)",
+                &loader
+            ),
+            parse_input(
+                "number(select=1..3) | literal(\".\") | number(select=1..3) | literal(\".\") | \
+                 number(select=1..3) | literal(\".\") | number(select=1..3) | literal(\":\") | \
+                 number(select=1..5)"
+            )
+        );
+        assert_eq!(loader.loads.get(), 3);
+
+        assert_eq!(
+            parse_loaded("import \"lib/net.rgx\" as net; octet", &loader),
+            Err(ErrorKind::UnknownFunction {
+                name: "octet".to_string(),
+                suggestion: None,
+            })
+        );
+        assert_eq!(
+            parse_loaded("import \"net.rgx\" as net; net.ip", &loader),
+            Err(ErrorKind::ImportFailed {
+                path: "net.rgx".to_string(),
+                reason: "no such file in memory".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_imports_disabled_by_default() {
+        // `Cargo.toml` exists where the tests run, and must not be read.
+        for path in ["x", "Cargo.toml"] {
+            let input = format!("import \"{path}\" as m; m");
+            let error = parse(tokens::tokenize(input).unwrap()).unwrap_err();
+
+            assert!(
+                matches!(&error.kind, ErrorKind::ImportFailed { path: failed, reason }
+                    if failed == path && reason.contains("imports are disabled")),
+                "{error:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let mut loader = crate::loader::MemoryLoader::new();
+        loader.insert("a.rgx", "import \"b.rgx\" as b;");
+        loader.insert("b.rgx", "import \"a.rgx\" as a;");
+        loader.insert("broken.rgx", "let x = bogus; def f(x) = x | oops;");
+        loader.insert("pattern.rgx", "letters");
+
+        assert_eq!(
+            parse_loaded("import \"a.rgx\" as a; letter", &loader),
+            Err(ErrorKind::InModule {
+                path: "a.rgx".to_string(),
+                line: 1,
+                column: 8,
+                message: "in \"b.rgx\" at 1:8: \"a.rgx\" ends up importing itself".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_loaded("import \"broken.rgx\" as broken; letter", &loader),
+            Err(ErrorKind::InModule {
+                path: "broken.rgx".to_string(),
+                line: 1,
+                column: 9,
+                message: "unknown function `bogus`".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_loaded("import \"pattern.rgx\" as p; letter", &loader),
+            Err(ErrorKind::InModule {
+                path: "pattern.rgx".to_string(),
+                line: 1,
+                column: 1,
+                message: "unexpected function `letters`, expected `let`, `def` or `import`"
+                    .to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "# a date
+            let year = number(select=4); # four digits
+            def quoted(x) = literal(\"'\") | x | literal(\"'\");
+            year | literal(\"-\") # separator
+            | quoted(
+                # the month
+                number(select=2)
+            )
+            # done";
+
+        let nodes = parse_input(input).unwrap();
+        let comments: Vec<Vec<&str>> = nodes
+            .iter()
+            .map(|node| node.comments.iter().map(String::as_str).collect())
+            .collect();
+
+        assert_eq!(
+            comments,
+            vec![
+                vec!["a date", "four digits"],
+                vec!["separator"],
+                vec![],
+                vec!["the month"],
+                vec!["done"],
             ]
         );
+        assert_eq!(
+            nodes,
+            parse_input(
+                "number(select=4) | literal(\"-\") | literal(\"'\") | number(select=2) | literal(\"'\")"
+            )
+            .unwrap()
+        );
     }
 }
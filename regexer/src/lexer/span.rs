@@ -0,0 +1,44 @@
+/// Location of a piece of the pattern source. `start` and `end` are byte
+/// offsets, `line` and `column` (both starting at 1) point at `start`.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        return Span {
+            start,
+            end,
+            line,
+            column,
+        };
+    }
+
+    /// Returns a span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        if other.start < self.start {
+            return other.to(*self);
+        }
+
+        return Span {
+            end: self.end.max(other.end),
+            ..*self
+        };
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Spanned<T> {
+        return Spanned { node, span };
+    }
+}
@@ -1,4 +1,7 @@
-use std::{iter::Peekable, str::Chars};
+use std::{iter::Peekable, num::IntErrorKind, str::Chars};
+
+use crate::error::{Error, ErrorKind};
+use crate::lexer::span::{Span, Spanned};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token {
@@ -7,25 +10,97 @@ pub enum Token {
     Int(u32),
     LeftParen,
     RightParen,
+    LeftBracket,
+    RightBracket,
     Whitespace,
     Equal,
     False,
     True,
     Pipe,
+    Plus,
+    Minus,
+    Ampersand,
     Comma,
+    Semicolon,
+    DotDot,
+    Let,
+    Def,
+    Import,
+    As,
+    String(String),
+    Comment(String),
+}
+
+/// Walks the source characters while keeping track of where we are in it.
+struct Cursor<'a> {
+    peeks: Peekable<Chars<'a>>,
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(line: &'a str) -> Cursor<'a> {
+        return Cursor {
+            peeks: line.chars().peekable(),
+            offset: 0,
+            line: 1,
+            column: 1,
+        };
+    }
+
+    fn peek(&mut self) -> Option<&char> {
+        return self.peeks.peek();
+    }
+
+    /// Character after the next one.
+    fn peek_second(&self) -> Option<char> {
+        return self.peeks.clone().nth(1);
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.peeks.next()?;
+
+        self.offset += ch.len_utf8();
+
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+
+        return Some(ch);
+    }
+
+    /// Empty span at the current position.
+    fn here(&self) -> Span {
+        return Span::new(self.offset, self.offset, self.line, self.column);
+    }
+
+    /// Span of a token that starts at `start` and ends at the current position.
+    fn span_from(&self, start: Span) -> Span {
+        return Span::new(start.start, self.offset, start.line, start.column);
+    }
 }
 
-pub fn tokenize(line: String) -> Vec<Token> {
-    let mut peeks = line.chars().peekable();
+pub fn tokenize(line: String) -> Result<Vec<Spanned<Token>>, Error> {
+    let mut peeks = Cursor::new(&line);
 
-    let mut tokens: Vec<Token> = vec![];
+    let mut tokens: Vec<Spanned<Token>> = vec![];
 
     while let Some(&symbol) = peeks.peek() {
-        match symbol {
-            symbol if symbol.is_alphabetic() => {
-                tokens.push(match read_string(&mut peeks).as_str() {
+        let start = peeks.here();
+
+        let token = match symbol {
+            symbol if symbol.is_alphabetic() || symbol == '_' => {
+                match read_string(&mut peeks).as_str() {
                     "True" => Token::True,
                     "False" => Token::False,
+                    "let" => Token::Let,
+                    "def" => Token::Def,
+                    "import" => Token::Import,
+                    "as" => Token::As,
                     s => {
                         if let Some('=') = &peeks.peek() {
                             Token::Parameter(s.to_string())
@@ -33,51 +108,80 @@ pub fn tokenize(line: String) -> Vec<Token> {
                             Token::Identifier(s.to_string())
                         }
                     }
-                });
-            }
-            symbol if symbol.is_alphanumeric() => {
-                if let Ok(num) = read_number(&mut peeks).as_str().parse::<u32>() {
-                    tokens.push(Token::Int(num));
                 }
             }
-            '(' => {
-                tokens.push(consume(Token::LeftParen, &mut peeks));
-            }
-            ')' => {
-                tokens.push(consume(Token::RightParen, &mut peeks));
-            }
-            '|' => {
-                tokens.push(consume(Token::Pipe, &mut peeks));
-            }
-            ' ' => {
-                tokens.push(consume(Token::Whitespace, &mut peeks));
-            }
-            '=' => {
-                tokens.push(consume(Token::Equal, &mut peeks));
+            symbol if symbol.is_alphanumeric() => {
+                let number = read_number(&mut peeks);
+
+                Token::Int(parse_number(number, peeks.span_from(start))?)
             }
-            ',' => {
-                tokens.push(consume(Token::Comma, &mut peeks));
+            '(' => consume(Token::LeftParen, &mut peeks),
+            ')' => consume(Token::RightParen, &mut peeks),
+            '[' => consume(Token::LeftBracket, &mut peeks),
+            ']' => consume(Token::RightBracket, &mut peeks),
+            '|' => consume(Token::Pipe, &mut peeks),
+            '+' => consume(Token::Plus, &mut peeks),
+            '-' => consume(Token::Minus, &mut peeks),
+            '&' => consume(Token::Ampersand, &mut peeks),
+            symbol if symbol.is_whitespace() => consume(Token::Whitespace, &mut peeks),
+            '#' => Token::Comment(read_comment(&mut peeks)),
+            '=' => consume(Token::Equal, &mut peeks),
+            ',' => consume(Token::Comma, &mut peeks),
+            ';' => consume(Token::Semicolon, &mut peeks),
+            '"' => Token::String(read_quoted(&mut peeks, start)?),
+            '.' => {
+                peeks.next();
+
+                if peeks.peek() != Some(&'.') {
+                    return Err(Error::new(
+                        ErrorKind::InvalidToken(symbol),
+                        peeks.span_from(start),
+                    ));
+                }
+
+                consume(Token::DotDot, &mut peeks)
             }
             _ => {
-                panic!("Invalid token -> [{symbol}]");
+                peeks.next();
+
+                return Err(Error::new(
+                    ErrorKind::InvalidToken(symbol),
+                    peeks.span_from(start),
+                ));
             }
-        }
+        };
+
+        tokens.push(Spanned::new(token, peeks.span_from(start)));
     }
 
-    return tokens;
+    return Ok(tokens);
 }
 
-fn consume(token: Token, peeks: &mut Peekable<Chars<'_>>) -> Token {
+fn parse_number(number: String, span: Span) -> Result<u32, Error> {
+    return number.parse::<u32>().map_err(|err| match err.kind() {
+        IntErrorKind::PosOverflow => Error::new(ErrorKind::IntOverflow(number.clone()), span),
+        _ => Error::new(ErrorKind::InvalidNumber(number.clone()), span),
+    });
+}
+
+fn consume(token: Token, peeks: &mut Cursor<'_>) -> Token {
     peeks.next();
 
     return token;
 }
 
-fn read_string(peeks: &mut Peekable<Chars<'_>>) -> String {
+/// Reads an identifier, which can hold digits after its first character
+/// and be qualified by the name of an import, like `common.ip_address`.
+fn read_string(peeks: &mut Cursor<'_>) -> String {
     let mut string = String::new();
 
     while let Some(&ch) = peeks.peek() {
-        if ch.is_alphabetic() {
+        let qualified = ch == '.'
+            && peeks
+                .peek_second()
+                .is_some_and(|next| next.is_alphabetic() || next == '_');
+
+        if ch.is_alphanumeric() || ch == '_' || qualified {
             string.push(ch);
             peeks.next();
         } else {
@@ -88,7 +192,108 @@ fn read_string(peeks: &mut Peekable<Chars<'_>>) -> String {
     return string;
 }
 
-fn read_number(peeks: &mut Peekable<Chars<'_>>) -> String {
+/// Reads a `#` comment up to the end of the line, returning its trimmed text
+/// without the control characters.
+fn read_comment(peeks: &mut Cursor<'_>) -> String {
+    let mut string = String::new();
+
+    peeks.next();
+
+    while let Some(&ch) = peeks.peek() {
+        if ch == '\n' {
+            break;
+        }
+
+        if !ch.is_control() {
+            string.push(ch);
+        }
+        peeks.next();
+    }
+
+    return string.trim().to_string();
+}
+
+/// Reads a `"` delimited string, returning what's between the quotes with
+/// the escape sequences resolved.
+fn read_quoted(peeks: &mut Cursor<'_>, start: Span) -> Result<String, Error> {
+    let mut string = String::new();
+
+    peeks.next();
+
+    loop {
+        let escape_start = peeks.here();
+
+        match peeks.next() {
+            Some('"') => return Ok(string),
+            Some('\\') => string.push(read_escape(peeks, escape_start)?),
+            Some(ch) => string.push(ch),
+            None => {
+                return Err(Error::new(
+                    ErrorKind::UnterminatedString,
+                    peeks.span_from(start),
+                ));
+            }
+        }
+    }
+}
+
+/// Reads what follows a `\` inside a string: `\"`, `\\`, `\n`, `\t`, `\r`,
+/// `\0` or `\u{...}` with the hexadecimal code of a character.
+fn read_escape(peeks: &mut Cursor<'_>, start: Span) -> Result<char, Error> {
+    let invalid = |peeks: &Cursor<'_>, sequence: String| {
+        Error::new(ErrorKind::InvalidEscape(sequence), peeks.span_from(start))
+    };
+
+    let ch = match peeks.next() {
+        Some('"') => '"',
+        Some('\\') => '\\',
+        Some('n') => '\n',
+        Some('t') => '\t',
+        Some('r') => '\r',
+        Some('0') => '\0',
+        Some('u') => {
+            let mut sequence = String::from("\\u");
+
+            if peeks.peek() != Some(&'{') {
+                return Err(invalid(peeks, sequence));
+            }
+
+            // Stops at the first character that can't be part of the
+            // escape, so an unterminated one doesn't take the rest of the
+            // input with it.
+            sequence.extend(peeks.next());
+
+            while let Some(&ch) = peeks.peek() {
+                if !ch.is_ascii_hexdigit() && ch != '}' {
+                    break;
+                }
+
+                sequence.extend(peeks.next());
+
+                if ch == '}' {
+                    break;
+                }
+            }
+
+            let code = sequence
+                .strip_prefix("\\u{")
+                .and_then(|rest| rest.strip_suffix('}'))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .and_then(char::from_u32);
+
+            match code {
+                Some(ch) => ch,
+                None => return Err(invalid(peeks, sequence)),
+            }
+        }
+        Some(ch) => return Err(invalid(peeks, format!("\\{ch}"))),
+        None => return Err(invalid(peeks, String::from("\\"))),
+    };
+
+    return Ok(ch);
+}
+
+fn read_number(peeks: &mut Cursor<'_>) -> String {
     let mut string = String::new();
 
     while let Some(&ch) = peeks.peek() {
@@ -107,6 +312,12 @@ fn read_number(peeks: &mut Peekable<Chars<'_>>) -> String {
 mod tests {
     use super::*;
 
+    fn kinds(tokens: Result<Vec<Spanned<Token>>, Error>) -> Result<Vec<Token>, ErrorKind> {
+        return tokens
+            .map(|tokens| tokens.into_iter().map(|token| token.node).collect())
+            .map_err(|error| error.kind);
+    }
+
     #[test]
     fn test_tokenize() {
         let sut = String::from("letter(upcase=True) | glob(rest=True) | whitespace | number");
@@ -137,7 +348,7 @@ mod tests {
             Token::Identifier("number".to_string()),
         ];
 
-        assert_eq!(tokenize(sut), expected);
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
 
         let sut = String::from("letters(upcase=True) | glob(rest=True) | whitespace | numbers");
 
@@ -167,7 +378,7 @@ mod tests {
             Token::Identifier("numbers".to_string()),
         ];
 
-        assert_eq!(tokenize(sut), expected);
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
 
         let sut =
             String::from("group(letter(upcase=True) | glob(rest=True) | whitespace) | number");
@@ -201,7 +412,7 @@ mod tests {
             Token::Identifier("number".to_string()),
         ];
 
-        assert_eq!(tokenize(sut), expected);
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
 
         let sut = String::from(
             "group(letters(upcase=True) | glob(rest=True)) | whitespace | group(numbers)",
@@ -239,7 +450,7 @@ mod tests {
             Token::RightParen,
         ];
 
-        assert_eq!(tokenize(sut), expected);
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
     }
 
     #[test]
@@ -260,7 +471,7 @@ mod tests {
             Token::RightParen,
         ];
 
-        assert_eq!(tokenize(sut), expected);
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
 
         let sut = String::from("letters(upcase=True, select=33)");
 
@@ -278,7 +489,7 @@ mod tests {
             Token::RightParen,
         ];
 
-        assert_eq!(tokenize(sut), expected);
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
 
         let sut = String::from("letters(upcase=True,select=33)");
 
@@ -295,6 +506,260 @@ mod tests {
             Token::RightParen,
         ];
 
-        assert_eq!(tokenize(sut), expected);
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+    }
+
+    #[test]
+    fn test_invalid_tokens() {
+        assert_eq!(
+            kinds(tokenize(String::from("letter | glob(rest=True) @ number"))),
+            Err(ErrorKind::InvalidToken('@'))
+        );
+
+        assert_eq!(
+            kinds(tokenize(String::from("number(select=4294967296)"))),
+            Err(ErrorKind::IntOverflow("4294967296".to_string()))
+        );
+
+        assert_eq!(
+            kinds(tokenize(String::from("number(select=3a)"))),
+            Err(ErrorKind::InvalidNumber("3a".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let tokens = tokenize(String::from("letter(select=12) | numbers")).unwrap();
+
+        let spans: Vec<(usize, usize)> = tokens
+            .iter()
+            .map(|token| (token.span.start, token.span.end))
+            .collect();
+
+        assert_eq!(
+            spans,
+            vec![
+                (0, 6),
+                (6, 7),
+                (7, 13),
+                (13, 14),
+                (14, 16),
+                (16, 17),
+                (17, 18),
+                (18, 19),
+                (19, 20),
+                (20, 27),
+            ]
+        );
+
+        assert_eq!(tokens[9].span, Span::new(20, 27, 1, 21));
+
+        let error = tokenize(String::from("number(select=3a)")).unwrap_err();
+
+        assert_eq!(error.span, Span::new(14, 16, 1, 15));
+    }
+
+    #[test]
+    fn test_identifier_with_underscore() {
+        let sut = String::from("one_of_patterns(letter, number)");
+
+        let expected = vec![
+            Token::Identifier("one_of_patterns".to_string()),
+            Token::LeftParen,
+            Token::Identifier("letter".to_string()),
+            Token::Comma,
+            Token::Whitespace,
+            Token::Identifier("number".to_string()),
+            Token::RightParen,
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+    }
+
+    #[test]
+    fn test_range() {
+        let sut = String::from("number(select=2..5)");
+
+        let expected = vec![
+            Token::Identifier("number".to_string()),
+            Token::LeftParen,
+            Token::Parameter("select".to_string()),
+            Token::Equal,
+            Token::Int(2),
+            Token::DotDot,
+            Token::Int(5),
+            Token::RightParen,
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+
+        assert_eq!(
+            kinds(tokenize(String::from("number(select=2.5)"))),
+            Err(ErrorKind::InvalidToken('.'))
+        );
+    }
+
+    #[test]
+    fn test_set_operators() {
+        let sut = String::from("(letter+number)&-");
+
+        let expected = vec![
+            Token::LeftParen,
+            Token::Identifier("letter".to_string()),
+            Token::Plus,
+            Token::Identifier("number".to_string()),
+            Token::RightParen,
+            Token::Ampersand,
+            Token::Minus,
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+    }
+
+    #[test]
+    fn test_list() {
+        let sut = String::from("letter(script=[Latin, Greek])");
+
+        let expected = vec![
+            Token::Identifier("letter".to_string()),
+            Token::LeftParen,
+            Token::Parameter("script".to_string()),
+            Token::Equal,
+            Token::LeftBracket,
+            Token::Identifier("Latin".to_string()),
+            Token::Comma,
+            Token::Whitespace,
+            Token::Identifier("Greek".to_string()),
+            Token::RightBracket,
+            Token::RightParen,
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+    }
+
+    #[test]
+    fn test_binding() {
+        let sut = String::from("let day_2 = number;day_2");
+
+        let expected = vec![
+            Token::Let,
+            Token::Whitespace,
+            Token::Identifier("day_2".to_string()),
+            Token::Whitespace,
+            Token::Equal,
+            Token::Whitespace,
+            Token::Identifier("number".to_string()),
+            Token::Semicolon,
+            Token::Identifier("day_2".to_string()),
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+    }
+
+    #[test]
+    fn test_import() {
+        let sut = String::from("import \"net.rgx\" as net;net.ip_v4|glob..");
+
+        let expected = vec![
+            Token::Import,
+            Token::Whitespace,
+            Token::String("net.rgx".to_string()),
+            Token::Whitespace,
+            Token::As,
+            Token::Whitespace,
+            Token::Identifier("net".to_string()),
+            Token::Semicolon,
+            Token::Identifier("net.ip_v4".to_string()),
+            Token::Pipe,
+            Token::Identifier("glob".to_string()),
+            Token::DotDot,
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+    }
+
+    #[test]
+    fn test_comments() {
+        let sut = String::from("letter\t# a letter\r\n|\n  # then a number\nnumber #");
+
+        let expected = vec![
+            Token::Identifier("letter".to_string()),
+            Token::Whitespace,
+            Token::Comment("a letter".to_string()),
+            Token::Whitespace,
+            Token::Pipe,
+            Token::Whitespace,
+            Token::Whitespace,
+            Token::Whitespace,
+            Token::Comment("then a number".to_string()),
+            Token::Whitespace,
+            Token::Identifier("number".to_string()),
+            Token::Whitespace,
+            Token::Comment("".to_string()),
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+    }
+
+    #[test]
+    fn test_string() {
+        let sut = String::from("group(letter, name=\"first name\")");
+
+        let expected = vec![
+            Token::Identifier("group".to_string()),
+            Token::LeftParen,
+            Token::Identifier("letter".to_string()),
+            Token::Comma,
+            Token::Whitespace,
+            Token::Parameter("name".to_string()),
+            Token::Equal,
+            Token::String("first name".to_string()),
+            Token::RightParen,
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+
+        assert_eq!(
+            kinds(tokenize(String::from("group(letter, name=\"year)"))),
+            Err(ErrorKind::UnterminatedString)
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let sut = String::from(r#"literal("\"v1.2\" \\ \n\t\u{e9}")"#);
+
+        let expected = vec![
+            Token::Identifier("literal".to_string()),
+            Token::LeftParen,
+            Token::String("\"v1.2\" \\ \n\t\u{e9}".to_string()),
+            Token::RightParen,
+        ];
+
+        assert_eq!(kinds(tokenize(sut)), Ok(expected));
+
+        assert_eq!(
+            kinds(tokenize(String::from(r#"literal("\d")"#))),
+            Err(ErrorKind::InvalidEscape(r"\d".to_string()))
+        );
+
+        assert_eq!(
+            kinds(tokenize(String::from(r#"literal("\u{110000}")"#))),
+            Err(ErrorKind::InvalidEscape(r"\u{110000}".to_string()))
+        );
+
+        assert_eq!(
+            tokenize(String::from(r#"literal("\u{41") | letters | numbers"#))
+                .map_err(|error| (error.kind, error.span)),
+            Err((
+                ErrorKind::InvalidEscape(r"\u{41".to_string()),
+                Span::new(9, 14, 1, 10)
+            ))
+        );
+
+        assert_eq!(
+            kinds(tokenize(String::from(r#"literal("\u{zz}")"#))),
+            Err(ErrorKind::InvalidEscape(r"\u{".to_string()))
+        );
     }
 }
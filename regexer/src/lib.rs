@@ -1,9 +1,36 @@
-mod lexer;
+mod error;
+pub mod lexer;
+mod loader;
+mod suggest;
 mod transpiler;
 
-pub fn parse(input: String) -> String {
-    let tokens = lexer::tokens::tokenize(input);
-    let ast = lexer::ast::parse(tokens);
+pub use error::{Error, ErrorKind};
+pub use lexer::span::Span;
+pub use loader::{FileLoader, Loader, MemoryLoader, NoLoader};
+pub use transpiler::{Flavor, Regex};
 
-    return transpiler::regex::transpile(ast);
+/// Transpiles `input` for the default flavor. Imports are refused, use
+/// [`compile`] with a [`FileLoader`] to read them from disk.
+pub fn parse(input: String) -> Result<String, Error> {
+    return parse_with_flavor(input, Flavor::default());
+}
+
+pub fn parse_with_flavor(input: String, flavor: Flavor) -> Result<String, Error> {
+    return Ok(parse_parts(input, flavor)?.to_string());
+}
+
+/// Same as [`parse_with_flavor`], but keeps the flags declared by
+/// `flags(...)` apart from the pattern.
+pub fn parse_parts(input: String, flavor: Flavor) -> Result<Regex, Error> {
+    return compile(input, flavor, &NoLoader);
+}
+
+/// Same as [`parse_parts`], reading the files given to `import` with
+/// `loader`. Each file is read and parsed once, however many times it's
+/// imported.
+pub fn compile(input: String, flavor: Flavor, loader: &dyn Loader) -> Result<Regex, Error> {
+    let tokens = lexer::tokens::tokenize(input)?;
+    let pattern = lexer::ast::parse_with_loader(tokens, loader)?;
+
+    return transpiler::regex::transpile_pattern(&pattern, flavor);
 }
@@ -0,0 +1,122 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// Where `import` gets the text of pattern files from.
+pub trait Loader {
+    /// Reads the file at `path`, already resolved against the directory of
+    /// the importing file.
+    fn load(&self, path: &Path) -> io::Result<String>;
+}
+
+/// Reads pattern files from disk. The paths imported by the pattern itself
+/// start from the working directory.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FileLoader;
+
+impl Loader for FileLoader {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        return std::fs::read_to_string(path);
+    }
+}
+
+/// Refuses every import, for patterns that mustn't read anything. This is
+/// what [`crate::parse`] and its variants use.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoLoader;
+
+impl Loader for NoLoader {
+    fn load(&self, _path: &Path) -> io::Result<String> {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            "imports are disabled, `regexer::compile` can enable them",
+        ));
+    }
+}
+
+/// Serves pattern files kept in memory, for callers that don't read their
+/// patterns from disk.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryLoader {
+    files: HashMap<PathBuf, String>,
+}
+
+impl MemoryLoader {
+    pub fn new() -> MemoryLoader {
+        return MemoryLoader::default();
+    }
+
+    pub fn insert(&mut self, path: impl AsRef<Path>, text: impl Into<String>) {
+        self.files.insert(normalize(path.as_ref()), text.into());
+    }
+}
+
+impl Loader for MemoryLoader {
+    fn load(&self, path: &Path) -> io::Result<String> {
+        return self
+            .files
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no such file in memory"));
+    }
+}
+
+/// Removes the `.` and resolves the `..` of `path` without looking at the
+/// filesystem, so that a file has the same path however it's imported.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut acc = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match acc.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    acc.pop();
+                }
+                Some(Component::RootDir | Component::Prefix(_)) => {}
+                _ => acc.push(".."),
+            },
+            component => acc.push(component),
+        }
+    }
+
+    return acc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(
+            normalize(Path::new("./lib/../common.rgx")),
+            PathBuf::from("common.rgx")
+        );
+        assert_eq!(
+            normalize(Path::new("../shared/./net.rgx")),
+            PathBuf::from("../shared/net.rgx")
+        );
+        assert_eq!(normalize(Path::new("/../a.rgx")), PathBuf::from("/a.rgx"));
+    }
+
+    #[test]
+    fn test_memory_loader() {
+        let mut loader = MemoryLoader::new();
+        loader.insert("lib/common.rgx", "let digit = number;");
+
+        assert_eq!(
+            loader.load(Path::new("lib/./common.rgx")).ok(),
+            Some(String::from("let digit = number;"))
+        );
+        assert!(loader.load(Path::new("common.rgx")).is_err());
+    }
+
+    #[test]
+    fn test_no_loader() {
+        assert_eq!(
+            NoLoader.load(Path::new("Cargo.toml")).unwrap_err().kind(),
+            io::ErrorKind::PermissionDenied
+        );
+    }
+}
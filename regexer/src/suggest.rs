@@ -0,0 +1,60 @@
+/// Picks the candidate closest to `name`, if one is close enough to be a typo
+/// of it: at most one edit away for short names, a third of their length for
+/// longer ones. Ties go to the alphabetically first candidate.
+pub fn closest<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<String> {
+    let limit = (name.chars().count() / 3).max(1);
+
+    return candidates
+        .into_iter()
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate.to_string());
+}
+
+/// Levenshtein distance: the number of characters to insert, remove or
+/// replace to turn `a` into `b`.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ch) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, other) in b.iter().enumerate() {
+            let replace = previous[j] + usize::from(ch != *other);
+            let remove = previous[j + 1] + 1;
+            let insert = current[j] + 1;
+
+            current.push(replace.min(remove).min(insert));
+        }
+
+        previous = current;
+    }
+
+    return previous[b.len()];
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        assert_eq!(distance("leters", "letters"), 1);
+        assert_eq!(distance("selct", "select"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("été", "ete"), 2);
+    }
+
+    #[test]
+    fn test_closest() {
+        let functions = ["letter", "letters", "number", "numbers", "end"];
+
+        assert_eq!(closest("leters", functions), Some(String::from("letters")));
+        assert_eq!(closest("nmber", functions), Some(String::from("number")));
+        assert_eq!(closest("and", functions), Some(String::from("end")));
+        assert_eq!(closest("group", functions), None);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::error::{Error, ErrorKind};
+use crate::lexer::ast::{Casing, Function, Node, Property};
+use crate::transpiler::Flavor;
+
+/// Rewrites `nodes` so they match regardless of case, for flavors without
+/// inline flags: `literal("ab")` becomes `[Aa][Bb]` and `letter(case="lower")`
+/// any letter.
+pub fn expand(nodes: &[Node], flavor: Flavor) -> Result<Vec<Node>, Error> {
+    let mut acc = vec![];
+
+    for node in nodes {
+        let Function::Literal(text) = &node.function else {
+            acc.push(expand_node(node, flavor)?);
+            continue;
+        };
+
+        let mut expanded = expand_literal(text, node);
+
+        if expanded.len() == 1 {
+            expanded[0].quantifier = node.quantifier;
+            acc.append(&mut expanded);
+        } else if node.quantifier.is_some() {
+            let mut group = Node::new(Function::NonCapturingGroup(expanded), node.span);
+            group.quantifier = node.quantifier;
+            acc.push(group);
+        } else {
+            acc.append(&mut expanded);
+        }
+    }
+
+    return Ok(acc);
+}
+
+fn expand_node(node: &Node, flavor: Flavor) -> Result<Node, Error> {
+    let function = match &node.function {
+        Function::Group(body) => Function::Group(expand(body, flavor)?),
+        Function::NamedGroup { name, body } => Function::NamedGroup {
+            name: name.clone(),
+            body: expand(body, flavor)?,
+        },
+        Function::NonCapturingGroup(body) | Function::IgnoreCase(body) => {
+            Function::NonCapturingGroup(expand(body, flavor)?)
+        }
+        Function::Either(branches) => Function::Either(
+            branches
+                .iter()
+                .map(|branch| expand(branch, flavor))
+                .collect::<Result<Vec<Vec<Node>>, Error>>()?,
+        ),
+        Function::Exact(body) => Function::Exact(expand(body, flavor)?),
+        Function::FollowedBy(body) => Function::FollowedBy(expand(body, flavor)?),
+        Function::NotFollowedBy(body) => Function::NotFollowedBy(expand(body, flavor)?),
+        Function::PrecededBy(body) => Function::PrecededBy(expand(body, flavor)?),
+        Function::NotPrecededBy(body) => Function::NotPrecededBy(expand(body, flavor)?),
+        Function::SameAs(_) => {
+            return Err(Error::new(
+                ErrorKind::Unsupported {
+                    feature: "case-insensitive backreferences",
+                    flavor,
+                },
+                node.span,
+            ));
+        }
+        _ => return Ok(expand_member(node)),
+    };
+
+    let mut expanded = node.clone();
+    expanded.function = function;
+
+    return Ok(expanded);
+}
+
+/// Adds the other case of the characters to a character class.
+fn expand_member(node: &Node) -> Node {
+    let function = match &node.function {
+        Function::Letter { .. } => Function::Letter {
+            casing: Casing::Any,
+        },
+        Function::Property(Property::Letter(_)) => {
+            Function::Property(Property::Letter(Casing::Any))
+        }
+        Function::Literal(chars) => {
+            let mut acc = chars.clone();
+
+            for ch in chars.chars() {
+                acc.extend(other_cases(ch));
+            }
+
+            Function::Literal(acc)
+        }
+        Function::Range(start, end) => {
+            let others: String = (*start..=*end)
+                .flat_map(other_cases)
+                .filter(|ch| !(*start..=*end).contains(ch))
+                .collect();
+
+            if others.is_empty() {
+                return node.clone();
+            }
+
+            Function::OneOf(vec![
+                node.clone(),
+                Node::new(Function::Literal(others), node.span),
+            ])
+        }
+        Function::OneOf(members) => Function::OneOf(members.iter().map(expand_member).collect()),
+        Function::NoneOf(members) => Function::NoneOf(members.iter().map(expand_member).collect()),
+        Function::Intersection(left, right) => Function::Intersection(
+            Box::new(expand_member(left)),
+            Box::new(expand_member(right)),
+        ),
+        Function::Difference(left, right) => Function::Difference(
+            Box::new(expand_member(left)),
+            Box::new(expand_member(right)),
+        ),
+        _ => return node.clone(),
+    };
+
+    let mut expanded = node.clone();
+    expanded.function = function;
+
+    return expanded;
+}
+
+/// Splits `text` into runs of caseless characters and classes like `[Aa]`.
+fn expand_literal(text: &str, node: &Node) -> Vec<Node> {
+    let mut acc = vec![];
+    let mut run = String::new();
+
+    for ch in text.chars() {
+        let others = other_cases(ch);
+
+        if others.is_empty() {
+            run.push(ch);
+            continue;
+        }
+
+        if !run.is_empty() {
+            acc.push(Node::new(
+                Function::Literal(std::mem::take(&mut run)),
+                node.span,
+            ));
+        }
+
+        let chars = std::iter::once(ch).chain(others).collect();
+        let member = Node::new(Function::Literal(chars), node.span);
+        acc.push(Node::new(Function::OneOf(vec![member]), node.span));
+    }
+
+    if !run.is_empty() {
+        acc.push(Node::new(Function::Literal(run), node.span));
+    }
+
+    return acc;
+}
+
+/// Other cases of `ch` that are a single character too.
+fn other_cases(ch: char) -> Vec<char> {
+    let mut acc = vec![];
+
+    for mut other in [
+        ch.to_lowercase().collect::<Vec<char>>(),
+        ch.to_uppercase().collect(),
+    ] {
+        if other.len() == 1 && other[0] != ch && !acc.contains(&other[0]) {
+            acc.push(other.remove(0));
+        }
+    }
+
+    return acc;
+}
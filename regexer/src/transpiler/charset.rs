@@ -0,0 +1,302 @@
+use crate::error::{Error, ErrorKind};
+use crate::lexer::ast::{Casing, Function, Node, Property, WhitespaceKind};
+
+/// Last Unicode scalar value.
+const MAX: u32 = char::MAX as u32;
+
+/// Every scalar value, which leaves out the surrogates.
+const SCALARS: [(u32, u32); 2] = [(0, 0xD7FF), (0xE000, MAX)];
+
+/// Classes the flavors have a shorthand for, like `\s`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Shorthand {
+    Whitespace,
+    Word,
+}
+
+impl Shorthand {
+    /// What the shorthand stands for once it has to be combined with other
+    /// sets: its ASCII characters.
+    fn ranges(&self) -> &'static [(u32, u32)] {
+        return match self {
+            Shorthand::Whitespace => &[(0x09, 0x0D), (0x20, 0x20)],
+            Shorthand::Word => &[(0x30, 0x39), (0x41, 0x5A), (0x5F, 0x5F), (0x61, 0x7A)],
+        };
+    }
+}
+
+/// Set of characters matched by a character class.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+pub struct CharSet {
+    /// Sorted ranges that neither overlap nor touch, both ends included.
+    ranges: Vec<(u32, u32)>,
+    /// Shorthands are left to the flavor as long as the set is only ever
+    /// extended.
+    shorthands: Vec<Shorthand>,
+    /// Unicode properties, which the set can hold but not compute with.
+    properties: Vec<Property>,
+}
+
+impl CharSet {
+    fn new(ranges: Vec<(u32, u32)>) -> CharSet {
+        return CharSet {
+            ranges: normalize(ranges),
+            ..CharSet::default()
+        };
+    }
+
+    /// Computes the characters matched by a node the parser accepted as a
+    /// character class. Only unions are possible with Unicode properties.
+    pub fn from_node(node: &Node) -> Result<CharSet, Error> {
+        let set = match &node.function {
+            Function::Letter { casing } => match casing {
+                Casing::Upcase => CharSet::new(vec![('A' as u32, 'Z' as u32)]),
+                Casing::Downcase => CharSet::new(vec![('a' as u32, 'z' as u32)]),
+                Casing::Any => {
+                    CharSet::new(vec![('A' as u32, 'Z' as u32), ('a' as u32, 'z' as u32)])
+                }
+            },
+            Function::Number => CharSet::new(vec![('0' as u32, '9' as u32)]),
+            Function::Whitespace { kind } => match kind {
+                WhitespaceKind::Any => CharSet::shorthand(Shorthand::Whitespace),
+                WhitespaceKind::Space => CharSet::new(vec![(0x20, 0x20)]),
+                WhitespaceKind::Tab => CharSet::new(vec![(0x09, 0x09)]),
+                WhitespaceKind::Newline => CharSet::new(vec![(0x0A, 0x0A)]),
+            },
+            Function::Word => CharSet::shorthand(Shorthand::Word),
+            Function::Alnum => CharSet::new(vec![(0x30, 0x39), (0x41, 0x5A), (0x61, 0x7A)]),
+            Function::HexDigit => CharSet::new(vec![(0x30, 0x39), (0x41, 0x46), (0x61, 0x66)]),
+            Function::Punctuation => {
+                CharSet::new(vec![(0x21, 0x2F), (0x3A, 0x40), (0x5B, 0x60), (0x7B, 0x7E)])
+            }
+            Function::AnyChar => CharSet::new(SCALARS.to_vec()),
+            Function::Property(property) => CharSet {
+                properties: vec![property.clone()],
+                ..CharSet::default()
+            },
+            Function::Literal(chars) => {
+                CharSet::new(chars.chars().map(|ch| (ch as u32, ch as u32)).collect())
+            }
+            Function::Range(start, end) => CharSet::new(vec![(*start as u32, *end as u32)]),
+            Function::OneOf(members) => CharSet::union_of(members)?,
+            Function::NoneOf(members) => {
+                computable(CharSet::union_of(members)?, node)?.complement()
+            }
+            Function::Intersection(left, right) => {
+                let left = computable(CharSet::from_node(left)?, node)?;
+
+                left.intersection(&computable(CharSet::from_node(right)?, node)?)
+            }
+            Function::Difference(left, right) => {
+                let left = computable(CharSet::from_node(left)?, node)?;
+
+                left.difference(&computable(CharSet::from_node(right)?, node)?)
+            }
+            _ => unreachable!("only character classes have a set, checked by the parser"),
+        };
+
+        return Ok(set);
+    }
+
+    fn shorthand(shorthand: Shorthand) -> CharSet {
+        return CharSet {
+            shorthands: vec![shorthand],
+            ..CharSet::default()
+        };
+    }
+
+    pub fn union_of(members: &[Node]) -> Result<CharSet, Error> {
+        let mut acc = CharSet::default();
+
+        for member in members {
+            acc = acc.union(&CharSet::from_node(member)?);
+        }
+
+        return Ok(acc);
+    }
+
+    pub fn ranges(&self) -> &[(u32, u32)] {
+        return &self.ranges;
+    }
+
+    pub fn shorthands(&self) -> &[Shorthand] {
+        return &self.shorthands;
+    }
+
+    pub fn properties(&self) -> &[Property] {
+        return &self.properties;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.ranges.is_empty() && self.shorthands.is_empty() && self.properties.is_empty();
+    }
+
+    /// Whether the set reaches both ends of Unicode, in which case it reads
+    /// better as the negation of its complement.
+    pub fn is_negative(&self) -> bool {
+        return self.shorthands.is_empty()
+            && self.properties.is_empty()
+            && self.ranges.first().is_some_and(|range| range.0 == 0)
+            && self.ranges.last().is_some_and(|range| range.1 == MAX);
+    }
+
+    pub fn union(&self, other: &CharSet) -> CharSet {
+        let mut shorthands = self.shorthands.clone();
+        let mut properties = self.properties.clone();
+
+        for shorthand in &other.shorthands {
+            if !shorthands.contains(shorthand) {
+                shorthands.push(*shorthand);
+            }
+        }
+
+        for property in &other.properties {
+            if !properties.contains(property) {
+                properties.push(property.clone());
+            }
+        }
+
+        return CharSet {
+            ranges: normalize([self.ranges.clone(), other.ranges.clone()].concat()),
+            shorthands,
+            properties,
+        };
+    }
+
+    pub fn intersection(&self, other: &CharSet) -> CharSet {
+        return CharSet::new(intersect(&self.materialize(), &other.materialize()));
+    }
+
+    pub fn difference(&self, other: &CharSet) -> CharSet {
+        return self.intersection(&other.complement());
+    }
+
+    pub fn complement(&self) -> CharSet {
+        let mut gaps = vec![];
+        let mut next = 0;
+
+        for (start, end) in self.materialize() {
+            if start > next {
+                gaps.push((next, start - 1));
+            }
+            next = end + 1;
+        }
+
+        if next <= MAX {
+            gaps.push((next, MAX));
+        }
+
+        return CharSet::new(intersect(&gaps, &SCALARS));
+    }
+
+    /// Ranges of the set with the shorthands spelled out.
+    fn materialize(&self) -> Vec<(u32, u32)> {
+        let mut ranges = self.ranges.clone();
+
+        for shorthand in &self.shorthands {
+            ranges.extend_from_slice(shorthand.ranges());
+        }
+
+        return normalize(ranges);
+    }
+}
+
+/// Makes sure `set` can be negated, intersected or subtracted from.
+fn computable(set: CharSet, node: &Node) -> Result<CharSet, Error> {
+    if !set.properties.is_empty() {
+        return Err(Error::new(ErrorKind::UncomputableSet, node.span));
+    }
+
+    return Ok(set);
+}
+
+fn normalize(mut ranges: Vec<(u32, u32)>) -> Vec<(u32, u32)> {
+    ranges.sort();
+
+    let mut acc: Vec<(u32, u32)> = vec![];
+
+    for (start, end) in ranges {
+        match acc.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => acc.push((start, end)),
+        }
+    }
+
+    return acc;
+}
+
+/// Intersects two normalized lists of ranges.
+fn intersect(left: &[(u32, u32)], right: &[(u32, u32)]) -> Vec<(u32, u32)> {
+    let mut acc = vec![];
+    let (mut i, mut j) = (0, 0);
+
+    while i < left.len() && j < right.len() {
+        let start = left[i].0.max(right[j].0);
+        let end = left[i].1.min(right[j].1);
+
+        if start <= end {
+            acc.push((start, end));
+        }
+
+        if left[i].1 < right[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+
+    return acc;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(ranges: &[(char, char)]) -> CharSet {
+        return CharSet::new(
+            ranges
+                .iter()
+                .map(|(start, end)| (*start as u32, *end as u32))
+                .collect(),
+        );
+    }
+
+    #[test]
+    fn test_operations() {
+        let letters = set(&[('a', 'z')]);
+        let vowels = set(&[('a', 'a'), ('e', 'e'), ('i', 'i'), ('o', 'o'), ('u', 'u')]);
+
+        assert_eq!(
+            set(&[('d', 'f'), ('a', 'c'), ('e', 'h')]),
+            set(&[('a', 'h')])
+        );
+        assert_eq!(letters.intersection(&vowels), vowels);
+        assert_eq!(
+            letters.difference(&vowels),
+            set(&[('b', 'd'), ('f', 'h'), ('j', 'n'), ('p', 't'), ('v', 'z')])
+        );
+        assert_eq!(letters.complement().complement(), letters);
+        assert!(letters.complement().is_negative());
+        assert!(letters.intersection(&set(&[('0', '9')])).is_empty());
+    }
+
+    #[test]
+    fn test_shorthands() {
+        let whitespace = CharSet::shorthand(Shorthand::Whitespace);
+        let word = CharSet::shorthand(Shorthand::Word);
+
+        assert_eq!(
+            whitespace.union(&set(&[('a', 'z')])).ranges(),
+            &[('a' as u32, 'z' as u32)]
+        );
+        assert_eq!(
+            whitespace.intersection(&set(&[(' ', 'a')])),
+            set(&[(' ', ' ')])
+        );
+        assert_eq!(
+            word.difference(&set(&[('_', '_')])),
+            set(&[('0', '9'), ('A', 'Z'), ('a', 'z')])
+        );
+        assert_eq!(word.union(&word).shorthands(), &[Shorthand::Word]);
+    }
+}
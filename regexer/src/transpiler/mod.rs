@@ -1 +1,106 @@
+use std::fmt;
+use std::str::FromStr;
+
+pub mod casefold;
+pub mod charset;
 pub mod regex;
+
+/// Regex engine the pattern is transpiled for.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Flavor {
+    #[default]
+    Pcre,
+    JavaScript,
+    Python,
+    /// The `regex` crate.
+    Rust,
+}
+
+impl Flavor {
+    pub const ALL: [Flavor; 4] = [
+        Flavor::Pcre,
+        Flavor::JavaScript,
+        Flavor::Python,
+        Flavor::Rust,
+    ];
+
+    /// Name used to pick the flavor, e.g. from the command line.
+    pub fn name(&self) -> &'static str {
+        return match self {
+            Flavor::Pcre => "pcre",
+            Flavor::JavaScript => "javascript",
+            Flavor::Python => "python",
+            Flavor::Rust => "rust",
+        };
+    }
+}
+
+/// A transpiled regex with its flags kept apart, for callers applying them
+/// themselves, e.g. with `new RegExp(pattern, flags)`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Regex {
+    pub pattern: String,
+    /// Flag letters understood by the flavor, e.g. `ms`.
+    pub flags: String,
+    pub flavor: Flavor,
+}
+
+/// Writes the regex with its flags the way the flavor expects: inline
+/// `(?ms)`, or always a `/.../ms` literal for JavaScript, flags or not.
+impl fmt::Display for Regex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.flavor {
+            // `//` would start a comment, `(?:)` matches the empty string too.
+            Flavor::JavaScript if self.pattern.is_empty() => write!(f, "/(?:)/{}", self.flags),
+            Flavor::JavaScript => write!(f, "/{}/{}", self.pattern.replace('/', r"\/"), self.flags),
+            _ if self.flags.is_empty() => write!(f, "{}", self.pattern),
+            Flavor::Pcre => {
+                // PCRE has no inline flag for UTF mode, only a leading verb.
+                let inline: String = self.flags.chars().filter(|flag| *flag != 'u').collect();
+
+                if self.flags.contains('u') {
+                    write!(f, "(*UTF)")?;
+                }
+
+                if !inline.is_empty() {
+                    write!(f, "(?{inline})")?;
+                }
+
+                write!(f, "{}", self.pattern)
+            }
+            Flavor::Python | Flavor::Rust => write!(f, "(?{}){}", self.flags, self.pattern),
+        }
+    }
+}
+
+impl fmt::Display for Flavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Flavor::Pcre => write!(f, "PCRE"),
+            Flavor::JavaScript => write!(f, "JavaScript"),
+            Flavor::Python => write!(f, "Python"),
+            Flavor::Rust => write!(f, "Rust"),
+        }
+    }
+}
+
+impl FromStr for Flavor {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Flavor, String> {
+        return match name.to_lowercase().as_str() {
+            "pcre" => Ok(Flavor::Pcre),
+            "javascript" | "js" => Ok(Flavor::JavaScript),
+            "python" | "py" => Ok(Flavor::Python),
+            "rust" => Ok(Flavor::Rust),
+            _ => {
+                let names: Vec<&str> = Flavor::ALL.iter().map(|flavor| flavor.name()).collect();
+
+                Err(format!(
+                    "unknown flavor `{name}`, expected one of: {}",
+                    names.join(", ")
+                ))
+            }
+        };
+    }
+}
@@ -1,64 +1,1172 @@
-use crate::lexer::ast::{Casing, Function};
-
-pub fn transpile(functions: Vec<Function>) -> String {
-    return functions.iter().fold(String::from(""), |mut acc, func| {
-        match func {
-            Function::Letter { casing, select } => {
-                match casing {
-                    Some(Casing::Upcase) => {
-                        acc.push_str("[A-Z]");
-                    }
-                    Some(Casing::Downcase) | None => {
-                        acc.push_str("[a-z]");
-                    }
-                }
+use crate::error::{Error, ErrorKind};
+use crate::lexer::ast::{
+    Casing, Flags, Function, Mode, Node, Pattern, Property, Quantifier, Reference, WhitespaceKind,
+};
+use crate::transpiler::casefold;
+use crate::transpiler::charset::{CharSet, Shorthand};
+use crate::transpiler::{Flavor, Regex};
 
-                match select {
-                    Some(num) => {
-                        acc.push_str(format!("{{{num}}}").as_str());
-                    }
-                    None => {}
-                }
+/// Delimit the comments of the nodes in the regex until `transpile_pattern`
+/// either writes them as `#` comments or drops them. Both are control
+/// characters, which regexer otherwise always escapes.
+const COMMENT_START: char = '\u{1}';
+const COMMENT_END: char = '\u{2}';
+
+/// Transpiles the whole pattern, leaving its flags apart.
+pub fn transpile_pattern(pattern: &Pattern, flavor: Flavor) -> Result<Regex, Error> {
+    let mut flags = transpile_flags(&pattern.flags.node, flavor)
+        .map_err(|kind| Error::new(kind, pattern.flags.span))?;
+
+    // Without `u`, JavaScript reads `\p{L}` as `p{L}` and splits the
+    // characters beyond U+FFFF in two inside brackets.
+    if flavor == Flavor::JavaScript && !flags.contains('u') && needs_unicode(&pattern.nodes) {
+        flags.push('u');
+    }
+
+    let mut regex = transpile(&pattern.nodes, flavor)?;
+
+    regex = match pattern.flags.node.extended {
+        true => escape_extended(&regex, flavor),
+        false => strip_comments(&regex),
+    };
+
+    return Ok(Regex {
+        pattern: regex,
+        flags,
+        flavor,
+    });
+}
+
+pub fn transpile(functions: &[Node], flavor: Flavor) -> Result<String, Error> {
+    return transpile_sequence(functions, true, flavor);
+}
+
+/// `delimited` tells whether something around the sequence (a group or the
+/// ends of the regex) already delimits it.
+fn transpile_sequence(
+    functions: &[Node],
+    delimited: bool,
+    flavor: Flavor,
+) -> Result<String, Error> {
+    let mut acc = String::new();
+    let mut previous: Option<&Node> = None;
+
+    for node in functions {
+        let transpiled = transpile_node(node, delimited && functions.len() == 1, flavor)?;
+
+        // `\1` followed by `0` would read as `\10`.
+        if let Some(Node {
+            function: Function::SameAs(Reference::Number(_)),
+            quantifier: None,
+            ..
+        }) = previous
+        {
+            if transpiled.starts_with(|ch: char| ch.is_ascii_digit()) {
+                let reference = acc.split_off(acc.rfind('\\').unwrap());
+                acc.push_str(&format!("(?:{reference})"));
             }
-            Function::Letters { casing } => match casing {
-                Some(Casing::Upcase) => {
-                    acc.push_str("[A-Z]+");
-                }
-                Some(Casing::Downcase) | None => {
-                    acc.push_str("[a-z]+");
-                }
-            },
-            Function::Glob { rest } => match rest {
-                true => {
-                    acc.push_str(".*");
-                }
-                false => {
-                    acc.push_str(".");
+        }
+
+        for comment in &node.comments {
+            acc.push(COMMENT_START);
+            acc.push_str(comment);
+            acc.push(COMMENT_END);
+        }
+
+        acc.push_str(&transpiled);
+        previous = Some(node);
+    }
+
+    return Ok(acc);
+}
+
+/// `alone` tells whether the node is all there is inside its group (or the
+/// whole regex), in which case it doesn't need to be delimited.
+fn transpile_node(node: &Node, alone: bool, flavor: Flavor) -> Result<String, Error> {
+    let mut acc = String::new();
+
+    match &node.function {
+        Function::Letter { casing } => match casing {
+            Casing::Upcase => {
+                acc.push_str("[A-Z]");
+            }
+            Casing::Downcase => {
+                acc.push_str("[a-z]");
+            }
+            Casing::Any => {
+                acc.push_str("[A-Za-z]");
+            }
+        },
+        Function::Glob => {
+            acc.push('.');
+        }
+        Function::Whitespace { kind } => match kind {
+            WhitespaceKind::Any => acc.push_str(r"\s"),
+            WhitespaceKind::Space => acc.push(' '),
+            WhitespaceKind::Tab => acc.push_str(r"\t"),
+            WhitespaceKind::Newline => acc.push_str(r"\n"),
+        },
+        Function::Word => {
+            acc.push_str(r"\w");
+        }
+        Function::Number => {
+            acc.push_str("[0-9]");
+        }
+        Function::Group(tokens) => {
+            acc.push('(');
+            acc.push_str(&transpile(tokens, flavor)?);
+            acc.push(')');
+        }
+        Function::NamedGroup { name, body } => {
+            match flavor {
+                Flavor::Pcre | Flavor::JavaScript => acc.push_str(&format!("(?<{name}>")),
+                Flavor::Python | Flavor::Rust => acc.push_str(&format!("(?P<{name}>")),
+            }
+            acc.push_str(&transpile(body, flavor)?);
+            acc.push(')');
+        }
+        Function::NonCapturingGroup(body) => {
+            acc.push_str("(?:");
+            acc.push_str(&transpile(body, flavor)?);
+            acc.push(')');
+        }
+        Function::Either(branches) => {
+            let branches = branches
+                .iter()
+                .map(|branch| transpile(branch, flavor))
+                .collect::<Result<Vec<String>, Error>>()?;
+
+            // When the alternation is all there is, the surrounding group
+            // (or the whole regex) already delimits it.
+            if alone && node.quantifier.is_none() {
+                acc.push_str(&branches.join("|"));
+            } else {
+                acc.push_str(&format!("(?:{})", branches.join("|")));
+            }
+        }
+        // JavaScript's `^` and `$` also match at line breaks under the `m`
+        // flag, so the ends of the input are spelled out with lookarounds.
+        Function::Start => match flavor {
+            Flavor::JavaScript => acc.push_str(r"(?<![\s\S])"),
+            Flavor::Pcre | Flavor::Python | Flavor::Rust => acc.push_str(r"\A"),
+        },
+        Function::End => match flavor {
+            Flavor::JavaScript => acc.push_str(r"(?![\s\S])"),
+            Flavor::Python => acc.push_str(r"\Z"),
+            Flavor::Pcre | Flavor::Rust => acc.push_str(r"\z"),
+        },
+        // JavaScript has no inline flags, so the line boundaries are spelled
+        // out with lookarounds.
+        Function::LineStart => match flavor {
+            Flavor::JavaScript => acc.push_str(r"(?<![^\n])"),
+            Flavor::Pcre | Flavor::Python | Flavor::Rust => acc.push_str("(?m:^)"),
+        },
+        Function::LineEnd => match flavor {
+            Flavor::JavaScript => acc.push_str(r"(?![^\n])"),
+            Flavor::Pcre | Flavor::Python | Flavor::Rust => acc.push_str("(?m:$)"),
+        },
+        Function::WordBoundary => {
+            acc.push_str(r"\b");
+        }
+        Function::NotWordBoundary => {
+            acc.push_str(r"\B");
+        }
+        Function::Literal(text) => {
+            let escaped: String = text.chars().map(|ch| escape_char(ch, flavor)).collect();
+
+            if node.quantifier.is_some() && text.chars().count() > 1 {
+                acc.push_str(&format!("(?:{escaped})"));
+            } else {
+                acc.push_str(&escaped);
+            }
+        }
+        Function::Property(Property::Letter(Casing::Any)) if flavor == Flavor::Python => {
+            // Word characters, minus digits and `_`, leaves the letters.
+            acc.push_str(r"[^\W\d_]");
+        }
+        Function::Property(property) => {
+            acc.push_str(&transpile_property(property, false, node, flavor)?);
+        }
+        Function::NoneOf(members) => {
+            acc.push_str(&transpile_negation(members, node, flavor)?);
+        }
+        Function::Alnum
+        | Function::HexDigit
+        | Function::Punctuation
+        | Function::AnyChar
+        | Function::Range(..)
+        | Function::OneOf(_)
+        | Function::Intersection(..)
+        | Function::Difference(..) => {
+            acc.push_str(&transpile_class(node, flavor)?);
+        }
+        Function::FollowedBy(body) => {
+            acc.push_str(&transpile_lookaround("(?=", body, node, flavor)?);
+        }
+        Function::NotFollowedBy(body) => {
+            acc.push_str(&transpile_lookaround("(?!", body, node, flavor)?);
+        }
+        Function::PrecededBy(body) => {
+            check_lookbehind(body, node, flavor)?;
+            acc.push_str(&transpile_lookaround("(?<=", body, node, flavor)?);
+        }
+        Function::NotPrecededBy(body) => {
+            check_lookbehind(body, node, flavor)?;
+            acc.push_str(&transpile_lookaround("(?<!", body, node, flavor)?);
+        }
+        Function::SameAs(reference) => match (reference, flavor) {
+            (_, Flavor::Rust) => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported {
+                        feature: "backreferences",
+                        flavor,
+                    },
+                    node.span,
+                ));
+            }
+            (Reference::Number(number), _) => acc.push_str(&format!("\\{number}")),
+            (Reference::Name(name), Flavor::Python) => acc.push_str(&format!("(?P={name})")),
+            (Reference::Name(name), _) => acc.push_str(&format!("\\k<{name}>")),
+        },
+        Function::IgnoreCase(body) => match flavor {
+            // JavaScript has no inline flags, so the cases are spelled out.
+            Flavor::JavaScript => {
+                let expanded = casefold::expand(body, flavor)?;
+
+                if node.quantifier.is_some() {
+                    acc.push_str(&format!("(?:{})", transpile(&expanded, flavor)?));
+                } else {
+                    acc.push_str(&transpile_sequence(&expanded, alone, flavor)?);
                 }
+            }
+            Flavor::Pcre | Flavor::Python | Flavor::Rust => {
+                acc.push_str(&format!("(?i:{})", transpile(body, flavor)?));
+            }
+        },
+        Function::Exact(body) => {
+            let start = Node::new(Function::Start, node.span);
+            let end = Node::new(Function::End, node.span);
+
+            acc.push_str(&transpile_node(&start, false, flavor)?);
+            acc.push_str(&transpile_sequence(body, false, flavor)?);
+            acc.push_str(&transpile_node(&end, false, flavor)?);
+        }
+    };
+
+    if let Some(quantifier) = &node.quantifier {
+        acc.push_str(&transpile_quantifier(node, quantifier, flavor)?);
+    }
+
+    return Ok(acc);
+}
+
+fn transpile_lookaround(
+    opening: &str,
+    body: &[Node],
+    node: &Node,
+    flavor: Flavor,
+) -> Result<String, Error> {
+    if flavor == Flavor::Rust {
+        return Err(Error::new(
+            ErrorKind::Unsupported {
+                feature: "lookarounds",
+                flavor,
             },
-            Function::Whitespace => {
-                acc.push_str(r"\s");
+            node.span,
+        ));
+    }
+
+    return Ok(format!("{opening}{})", transpile(body, flavor)?));
+}
+
+/// Makes sure the engine can tell how far back the lookbehind `body` starts:
+/// PCRE needs each top-level branch to have a fixed width, Python the whole
+/// body, while JavaScript accepts any width.
+fn check_lookbehind(body: &[Node], node: &Node, flavor: Flavor) -> Result<(), Error> {
+    let fixed = match (flavor, body) {
+        (Flavor::JavaScript | Flavor::Rust, _) => true,
+        (Flavor::Pcre, [alone]) => match &alone.function {
+            Function::Either(branches) if alone.quantifier.is_none() => {
+                branches.iter().all(|branch| fixed_width(branch).is_some())
             }
-            Function::Number { select } => {
-                acc.push_str("[0-9]");
+            _ => fixed_width(body).is_some(),
+        },
+        (Flavor::Pcre | Flavor::Python, _) => fixed_width(body).is_some(),
+    };
+
+    if !fixed {
+        return Err(Error::new(
+            ErrorKind::VariableLookbehind { flavor },
+            node.span,
+        ));
+    }
+
+    return Ok(());
+}
 
-                match select {
-                    Some(num) => {
-                        acc.push_str(format!("{{{num}}}").as_str());
+/// Number of characters matched by `nodes`, if it doesn't depend on the input.
+fn fixed_width(nodes: &[Node]) -> Option<u32> {
+    let mut width: u32 = 0;
+
+    for node in nodes {
+        let node_width = match &node.function {
+            Function::Letter { .. }
+            | Function::Glob
+            | Function::Whitespace { .. }
+            | Function::Word
+            | Function::Alnum
+            | Function::HexDigit
+            | Function::Punctuation
+            | Function::AnyChar
+            | Function::Number
+            | Function::Property(_)
+            | Function::Range(..)
+            | Function::OneOf(_)
+            | Function::NoneOf(_)
+            | Function::Intersection(..)
+            | Function::Difference(..) => 1,
+            Function::Literal(text) => text.chars().count() as u32,
+            Function::Group(body)
+            | Function::NamedGroup { body, .. }
+            | Function::NonCapturingGroup(body)
+            | Function::Exact(body)
+            | Function::IgnoreCase(body) => fixed_width(body)?,
+            Function::Either(branches) => {
+                let first = fixed_width(&branches[0])?;
+
+                for branch in &branches[1..] {
+                    if fixed_width(branch)? != first {
+                        return None;
                     }
-                    None => {}
                 }
+
+                first
             }
-            Function::Numbers => {
-                acc.push_str("[0-9]+");
-            }
-            Function::Group(tokens) => {
-                acc.push_str("(");
-                acc.push_str(&transpile(tokens.to_vec()));
-                acc.push_str(")");
+            Function::Start
+            | Function::End
+            | Function::LineStart
+            | Function::LineEnd
+            | Function::WordBoundary
+            | Function::NotWordBoundary
+            | Function::FollowedBy(_)
+            | Function::NotFollowedBy(_)
+            | Function::PrecededBy(_)
+            | Function::NotPrecededBy(_) => 0,
+            // The width of the referenced group would be needed.
+            Function::SameAs(_) => return None,
+        };
+
+        let repeated = match &node.quantifier {
+            None => node_width,
+            Some(quantifier) if quantifier.max == Some(quantifier.min) => {
+                node_width.checked_mul(quantifier.min)?
             }
+            Some(_) if node_width == 0 => 0,
+            Some(_) => return None,
         };
 
-        acc
+        width = width.checked_add(repeated)?;
+    }
+
+    return Some(width);
+}
+
+/// Writes the letters of the flags the way the flavor names them.
+fn transpile_flags(flags: &Flags, flavor: Flavor) -> Result<String, ErrorKind> {
+    if flags.extended && flavor == Flavor::JavaScript {
+        return Err(ErrorKind::Unsupported {
+            feature: "extended mode",
+            flavor,
+        });
+    }
+
+    let letters = [
+        (flags.ignore_case, 'i'),
+        (flags.multiline, 'm'),
+        (flags.dotall, 's'),
+        (flags.extended, 'x'),
+        (flags.unicode, 'u'),
+    ];
+
+    return Ok(letters
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, letter)| *letter)
+        .collect());
+}
+
+/// Whether the nodes use a Unicode property or a character beyond U+FFFF.
+fn needs_unicode(nodes: &[Node]) -> bool {
+    return nodes.iter().any(|node| match &node.function {
+        Function::Property(_) => true,
+        Function::Literal(text) => text.chars().any(|ch| ch > '\u{FFFF}'),
+        Function::Range(_, end) => *end > '\u{FFFF}',
+        Function::Group(body)
+        | Function::NamedGroup { body, .. }
+        | Function::NonCapturingGroup(body)
+        | Function::Exact(body)
+        | Function::FollowedBy(body)
+        | Function::NotFollowedBy(body)
+        | Function::PrecededBy(body)
+        | Function::NotPrecededBy(body)
+        | Function::IgnoreCase(body)
+        | Function::OneOf(body)
+        | Function::NoneOf(body) => needs_unicode(body),
+        Function::Either(branches) => branches.iter().any(|branch| needs_unicode(branch)),
+        Function::Intersection(left, right) | Function::Difference(left, right) => {
+            needs_unicode(std::slice::from_ref(left)) || needs_unicode(std::slice::from_ref(right))
+        }
+        _ => false,
     });
 }
+
+/// In extended mode the engine skips whitespace and `#` comments, so the
+/// ones the regex means literally are escaped. Everything else regexer writes
+/// is free of them, but for the comments of the nodes, written on their own
+/// lines.
+fn escape_extended(regex: &str, flavor: Flavor) -> String {
+    let mut acc = String::new();
+    let mut in_comment = false;
+
+    for ch in regex.chars() {
+        match ch {
+            COMMENT_START => {
+                if !acc.is_empty() && !acc.ends_with('\n') {
+                    acc.push('\n');
+                }
+
+                acc.push_str("# ");
+                in_comment = true;
+            }
+            COMMENT_END => {
+                acc.push('\n');
+                in_comment = false;
+            }
+            ch if in_comment => acc.push(ch),
+            ' ' | '#' => acc.push_str(&format!("\\{ch}")),
+            ch if ch.is_whitespace() => acc.push_str(&escape_code(ch, flavor)),
+            ch => acc.push(ch),
+        }
+    }
+
+    return acc;
+}
+
+fn strip_comments(regex: &str) -> String {
+    let mut acc = String::new();
+    let mut in_comment = false;
+
+    for ch in regex.chars() {
+        match ch {
+            COMMENT_START => in_comment = true,
+            COMMENT_END => in_comment = false,
+            ch if !in_comment => acc.push(ch),
+            _ => {}
+        }
+    }
+
+    return acc;
+}
+
+/// Escapes `ch` so it matches itself outside of a bracket expression.
+fn escape_char(ch: char, flavor: Flavor) -> String {
+    return match ch {
+        '\\' | '^' | '$' | '.' | '|' | '?' | '*' | '+' | '(' | ')' | '[' | ']' | '{' | '}' => {
+            format!("\\{ch}")
+        }
+        '\n' => String::from(r"\n"),
+        '\t' => String::from(r"\t"),
+        '\r' => String::from(r"\r"),
+        ch if ch.is_control() => escape_code(ch, flavor),
+        ch => ch.to_string(),
+    };
+}
+
+/// Writes the single bracket expression matching the characters of `node`.
+fn transpile_class(node: &Node, flavor: Flavor) -> Result<String, Error> {
+    let set = CharSet::from_node(node)?;
+
+    if set.is_empty() {
+        return Err(Error::new(ErrorKind::EmptySet, node.span));
+    }
+
+    if !set.is_negative() {
+        return Ok(format!("[{}]", transpile_class_body(&set, node, flavor)?));
+    }
+
+    let complement = set.complement();
+
+    if complement.is_empty() {
+        return Ok(String::from(r"[\s\S]"));
+    }
+
+    return Ok(format!(
+        "[^{}]",
+        transpile_class_body(&complement, node, flavor)?
+    ));
+}
+
+/// Writes `none_of(members)`, keeping the flavor's own shorthands.
+fn transpile_negation(members: &[Node], node: &Node, flavor: Flavor) -> Result<String, Error> {
+    match members {
+        [Node {
+            function:
+                Function::Whitespace {
+                    kind: WhitespaceKind::Any,
+                },
+            ..
+        }] => return Ok(String::from(r"\S")),
+        [Node {
+            function: Function::Word,
+            ..
+        }] => return Ok(String::from(r"\W")),
+        [Node {
+            function: Function::Property(property),
+            ..
+        }] => return transpile_property(property, true, node, flavor),
+        _ => {}
+    }
+
+    let union = CharSet::union_of(members)?;
+
+    // Negating the members leaves the shorthands and the properties for the
+    // flavor to interpret.
+    if !union.shorthands().is_empty() || !union.properties().is_empty() {
+        return Ok(format!(
+            "[^{}]",
+            transpile_class_body(&union, node, flavor)?
+        ));
+    }
+
+    return transpile_class(node, flavor);
+}
+
+/// Writes what goes between the brackets of a class matching `set`.
+fn transpile_class_body(set: &CharSet, node: &Node, flavor: Flavor) -> Result<String, Error> {
+    let mut acc = String::new();
+
+    for &(start, end) in set.ranges() {
+        // Ranges never start or end on a surrogate, which aren't characters.
+        let escape = |code| escape_class_char(char::from_u32(code).unwrap(), flavor);
+
+        acc.push_str(&escape(start));
+
+        match end - start {
+            0 => {}
+            1 => acc.push_str(&escape(end)),
+            _ => acc.push_str(&format!("-{}", escape(end))),
+        }
+    }
+
+    for shorthand in set.shorthands() {
+        match shorthand {
+            Shorthand::Whitespace => acc.push_str(r"\s"),
+            Shorthand::Word => acc.push_str(r"\w"),
+        }
+    }
+
+    for property in set.properties() {
+        acc.push_str(&transpile_property(property, false, node, flavor)?);
+    }
+
+    return Ok(acc);
+}
+
+/// Writes `\p{...}`, or `\P{...}` when `negated`. Python's `re` only knows
+/// the Unicode digits, through `\d`.
+fn transpile_property(
+    property: &Property,
+    negated: bool,
+    node: &Node,
+    flavor: Flavor,
+) -> Result<String, Error> {
+    let name = match (property, flavor) {
+        (Property::Number, Flavor::Python) => {
+            return Ok(String::from(if negated { r"\D" } else { r"\d" }));
+        }
+        (_, Flavor::Python) => {
+            return Err(Error::new(
+                ErrorKind::Unsupported {
+                    feature: "Unicode properties",
+                    flavor,
+                },
+                node.span,
+            ));
+        }
+        (Property::Letter(Casing::Upcase), _) => String::from("Lu"),
+        (Property::Letter(Casing::Downcase), _) => String::from("Ll"),
+        (Property::Letter(Casing::Any), _) => String::from("L"),
+        (Property::Number, _) => String::from("Nd"),
+        (Property::Script(script), Flavor::JavaScript) => format!("Script={script}"),
+        (Property::Script(script), _) => script.clone(),
+    };
+
+    return Ok(format!("\\{}{{{name}}}", if negated { 'P' } else { 'p' }));
+}
+
+/// Escapes `ch` so it matches itself inside a bracket expression.
+fn escape_class_char(ch: char, flavor: Flavor) -> String {
+    return match (ch, flavor) {
+        ('\\' | ']' | '[' | '^' | '-', _) => format!("\\{ch}"),
+        // Rust uses `&&` and `~~` for set operations inside brackets.
+        ('&' | '~', Flavor::Rust) => format!("\\{ch}"),
+        ('\n', _) => String::from(r"\n"),
+        ('\t', _) => String::from(r"\t"),
+        ('\r', _) => String::from(r"\r"),
+        (ch, flavor) if ch.is_control() => escape_code(ch, flavor),
+        (ch, _) => ch.to_string(),
+    };
+}
+
+/// Writes `ch` by its code, for characters that can't appear as is.
+fn escape_code(ch: char, flavor: Flavor) -> String {
+    let code = ch as u32;
+
+    return match flavor {
+        Flavor::Pcre | Flavor::Rust => format!("\\x{{{code:X}}}"),
+        Flavor::JavaScript | Flavor::Python if code <= 0xFF => format!("\\x{code:02X}"),
+        Flavor::JavaScript => format!("\\u{code:04X}"),
+        Flavor::Python => format!("\\U{code:08X}"),
+    };
+}
+
+fn transpile_quantifier(
+    node: &Node,
+    quantifier: &Quantifier,
+    flavor: Flavor,
+) -> Result<String, Error> {
+    let mut acc = match (quantifier.min, quantifier.max) {
+        (1, Some(1)) => return Ok(String::new()),
+        (0, Some(1)) => String::from("?"),
+        (0, None) => String::from("*"),
+        (1, None) => String::from("+"),
+        (min, Some(max)) if min == max => format!("{{{min}}}"),
+        (min, Some(max)) => format!("{{{min},{max}}}"),
+        (min, None) => format!("{{{min},}}"),
+    };
+
+    match quantifier.mode {
+        Mode::Greedy => {}
+        Mode::Lazy => acc.push('?'),
+        Mode::Possessive => match flavor {
+            Flavor::Pcre | Flavor::Python => acc.push('+'),
+            Flavor::JavaScript | Flavor::Rust => {
+                return Err(Error::new(
+                    ErrorKind::Unsupported {
+                        feature: "possessive quantifiers",
+                        flavor,
+                    },
+                    node.span,
+                ));
+            }
+        },
+    }
+
+    return Ok(acc);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::{ast, tokens};
+
+    fn regex(input: &str) -> String {
+        return regex_for(input, Flavor::Pcre).unwrap();
+    }
+
+    fn regex_for(input: &str, flavor: Flavor) -> Result<String, ErrorKind> {
+        let tokens = tokens::tokenize(input.to_string()).unwrap();
+
+        return transpile_pattern(&ast::parse(tokens).unwrap(), flavor)
+            .map(|regex| regex.to_string())
+            .map_err(|error| error.kind);
+    }
+
+    #[test]
+    fn test_either() {
+        assert_eq!(regex("either(letters, numbers)"), "[A-Za-z]+|[0-9]+");
+        assert_eq!(
+            regex("whitespace | either(letters, numbers) | whitespace"),
+            r"\s(?:[A-Za-z]+|[0-9]+)\s"
+        );
+        assert_eq!(
+            regex("group(either(letters | whitespace, numbers))"),
+            r"([A-Za-z]+\s|[0-9]+)"
+        );
+        assert_eq!(
+            regex("group(either(letters, numbers) | whitespace)"),
+            r"((?:[A-Za-z]+|[0-9]+)\s)"
+        );
+    }
+
+    #[test]
+    fn test_quantifiers() {
+        assert_eq!(regex("letter(case=\"upper\", select=3)"), "[A-Z]{3}");
+        assert_eq!(regex("letters(case=\"upper\", select=3)"), "[A-Z]{3}");
+        assert_eq!(regex("number(select=2..5)"), "[0-9]{2,5}");
+        assert_eq!(regex("number(select=2..)"), "[0-9]{2,}");
+        assert_eq!(regex("number(min=2)"), "[0-9]{2,}");
+        assert_eq!(regex("number(max=4)"), "[0-9]{0,4}");
+        assert_eq!(regex("number(min=1, max=1)"), "[0-9]");
+        assert_eq!(regex("whitespace(optional=True)"), r"\s?");
+        assert_eq!(regex("whitespace(many=True)"), r"\s+");
+        assert_eq!(regex("whitespace(optional=True, many=True)"), r"\s*");
+        assert_eq!(regex("letters(optional=True)"), "[A-Za-z]*");
+        assert_eq!(regex("glob(rest=True)"), ".*");
+        assert_eq!(regex("glob(rest=False, select=3)"), ".{3}");
+        assert_eq!(
+            regex("group(letter | number, select=2)"),
+            "([A-Za-z][0-9]){2}"
+        );
+        assert_eq!(
+            regex("either(letter, number, optional=True)"),
+            "(?:[A-Za-z]|[0-9])?"
+        );
+    }
+
+    #[test]
+    fn test_quantifier_modes() {
+        assert_eq!(regex("glob(rest=True, lazy=True)"), ".*?");
+        assert_eq!(regex("letters(greedy=False)"), "[A-Za-z]+?");
+        assert_eq!(regex("number(select=2..5, lazy=True)"), "[0-9]{2,5}?");
+        assert_eq!(regex("numbers(possessive=True)"), "[0-9]++");
+        assert_eq!(regex("whitespace(optional=True, possessive=True)"), r"\s?+");
+        assert_eq!(
+            regex_for("numbers(possessive=True)", Flavor::Python),
+            Ok("[0-9]++".to_string())
+        );
+        assert_eq!(
+            regex_for("glob(rest=True, lazy=True)", Flavor::Rust),
+            Ok(".*?".to_string())
+        );
+
+        for flavor in [Flavor::JavaScript, Flavor::Rust] {
+            assert_eq!(
+                regex_for("numbers(possessive=True)", flavor),
+                Err(ErrorKind::Unsupported {
+                    feature: "possessive quantifiers",
+                    flavor,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_groups() {
+        let input = "group(number(select=4), name=\"year\") | group(letters, capture=False)";
+
+        assert_eq!(regex(input), "(?<year>[0-9]{4})(?:[A-Za-z]+)");
+        assert_eq!(
+            regex_for(input, Flavor::Python),
+            Ok("(?P<year>[0-9]{4})(?:[A-Za-z]+)".to_string())
+        );
+        assert_eq!(
+            regex("group(letter | number, capture=False, optional=True)"),
+            "(?:[A-Za-z][0-9])?"
+        );
+    }
+
+    #[test]
+    fn test_anchors() {
+        assert_eq!(regex("exact(letters | numbers)"), r"\A[A-Za-z]+[0-9]+\z");
+        assert_eq!(
+            regex("exact(either(letters, numbers))"),
+            r"\A(?:[A-Za-z]+|[0-9]+)\z"
+        );
+        assert_eq!(
+            regex_for("exact(letters)", Flavor::JavaScript),
+            Ok(r"/(?<![\s\S])[A-Za-z]+(?![\s\S])/".to_string())
+        );
+        assert_eq!(
+            regex_for("flags(multiline=True) | exact(letters)", Flavor::JavaScript),
+            Ok(r"/(?<![\s\S])[A-Za-z]+(?![\s\S])/m".to_string())
+        );
+        assert_eq!(
+            regex_for("exact(letters)", Flavor::Python),
+            Ok(r"\A[A-Za-z]+\Z".to_string())
+        );
+        assert_eq!(
+            regex("word_boundary | letters | not_word_boundary"),
+            r"\b[A-Za-z]+\B"
+        );
+        assert_eq!(
+            regex("line_start | numbers | line_end"),
+            "(?m:^)[0-9]+(?m:$)"
+        );
+        assert_eq!(
+            regex_for("line_start | numbers | line_end", Flavor::JavaScript),
+            Ok(r"/(?<![^\n])[0-9]+(?![^\n])/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(regex("literal(\"v1.2\")"), r"v1\.2");
+        assert_eq!(regex("literal(\"(foo)\")"), r"\(foo\)");
+        assert_eq!(regex("literal(\"a+b*c?\")"), r"a\+b\*c\?");
+        assert_eq!(regex("literal(\"[^$]{1}|\\\\\")"), r"\[\^\$\]\{1\}\|\\");
+        assert_eq!(regex("literal(\"ab\", many=True)"), "(?:ab)+");
+        assert_eq!(regex("literal(\"a\", many=True)"), "a+");
+        assert_eq!(regex("literal(\"\\t\\n\")"), r"\t\n");
+        assert_eq!(regex("literal(\"\\0\")"), r"\x{0}");
+        assert_eq!(
+            regex_for("literal(\"\\0\")", Flavor::JavaScript),
+            Ok(r"/\x00/".to_string())
+        );
+    }
+
+    #[test]
+    fn test_character_classes() {
+        assert_eq!(regex("one_of(\"-_.\")"), r"[\-._]");
+        assert_eq!(regex("none_of(\"\\\"\")"), "[^\"]");
+        assert_eq!(regex("range(\"a\", \"f\", select=2)"), "[a-f]{2}");
+        assert_eq!(
+            regex("one_of(\"_\", range(\"a\", \"f\"), number, letter(case=\"upper\"), many=True)"),
+            "[0-9A-Z_a-f]+"
+        );
+        assert_eq!(regex("none_of(\"]^\\\\\", whitespace)"), r"[^\\-\^\s]");
+        assert_eq!(regex("one_of(\"&~\")"), "[&~]");
+        assert_eq!(
+            regex_for("one_of(\"&~\")", Flavor::Rust),
+            Ok(r"[\&\~]".to_string())
+        );
+    }
+
+    #[test]
+    fn test_set_operations() {
+        assert_eq!(
+            regex("letter(case=\"lower\") - one_of(\"aeiou\")"),
+            "[b-df-hj-np-tv-z]"
+        );
+        assert_eq!(
+            regex("(letter(case=\"upper\") + number) & none_of(\"0O\")"),
+            "[1-9A-NP-Z]"
+        );
+        assert_eq!(regex("number + letter + one_of(\"_\")"), "[0-9A-Z_a-z]");
+        assert_eq!(regex("none_of(\"x\") & letter"), "[A-Za-wyz]");
+        assert_eq!(regex("none_of(\"x\") - number"), "[^0-9x]");
+        assert_eq!(regex("whitespace - one_of(\"\\n\")"), r"[\t\x{B}-\r ]");
+        assert_eq!(
+            regex_for("letter & number", Flavor::Pcre),
+            Err(ErrorKind::EmptySet)
+        );
+    }
+
+    #[test]
+    fn test_not() {
+        assert_eq!(regex("not(number)"), "[^0-9]");
+        assert_eq!(regex("not(whitespace, many=True)"), r"\S+");
+        assert_eq!(regex("not(one_of(\"ab\", whitespace))"), r"[^ab\s]");
+        assert_eq!(regex("not(none_of(\"ab\"))"), "[ab]");
+        assert_eq!(
+            regex("letter(case=\"lower\") & not(one_of(\"aeiou\"))"),
+            "[b-df-hj-np-tv-z]"
+        );
+    }
+
+    #[test]
+    fn test_lookarounds() {
+        assert_eq!(
+            regex("preceded_by(literal(\"$\")) | numbers | not_followed_by(letter)"),
+            r"(?<=\$)[0-9]+(?![A-Za-z])"
+        );
+        assert_eq!(
+            regex("followed_by(glob(rest=True) | number) | not_preceded_by(number(select=2))"),
+            "(?=.*[0-9])(?<![0-9]{2})"
+        );
+        assert_eq!(
+            regex("preceded_by(either(literal(\"a\"), literal(\"bc\")))"),
+            "(?<=a|bc)"
+        );
+        assert_eq!(
+            regex_for(
+                "preceded_by(either(literal(\"a\"), literal(\"bc\")))",
+                Flavor::Python
+            ),
+            Err(ErrorKind::VariableLookbehind {
+                flavor: Flavor::Python
+            })
+        );
+        assert_eq!(
+            regex_for("preceded_by(numbers)", Flavor::Pcre),
+            Err(ErrorKind::VariableLookbehind {
+                flavor: Flavor::Pcre
+            })
+        );
+        assert_eq!(
+            regex_for("preceded_by(numbers)", Flavor::JavaScript),
+            Ok("/(?<=[0-9]+)/".to_string())
+        );
+        assert_eq!(
+            regex_for("followed_by(letter)", Flavor::Rust),
+            Err(ErrorKind::Unsupported {
+                feature: "lookarounds",
+                flavor: Flavor::Rust
+            })
+        );
+    }
+
+    #[test]
+    fn test_backreferences() {
+        let quotes = "group(one_of(\"'\\\"\"), name=\"quote\") | letters | same_as(\"quote\")";
+
+        assert_eq!(regex(quotes), r#"(?<quote>["'])[A-Za-z]+\k<quote>"#);
+        assert_eq!(
+            regex_for(quotes, Flavor::Python),
+            Ok(r#"(?P<quote>["'])[A-Za-z]+(?P=quote)"#.to_string())
+        );
+        assert_eq!(
+            regex("group(letters) | whitespace | same_as(1)"),
+            r"([A-Za-z]+)\s\1"
+        );
+        assert_eq!(
+            regex("group(number) | same_as(1) | literal(\"0\")"),
+            r"([0-9])(?:\1)0"
+        );
+        assert_eq!(
+            regex_for("group(letter) | same_as(1)", Flavor::Rust),
+            Err(ErrorKind::Unsupported {
+                feature: "backreferences",
+                flavor: Flavor::Rust
+            })
+        );
+    }
+
+    #[test]
+    fn test_unicode_classes() {
+        assert_eq!(
+            regex("letters(unicode=True, case=\"upper\") | letter(unicode=True)"),
+            r"\p{Lu}+\p{L}"
+        );
+        assert_eq!(regex("numbers(unicode=True)"), r"\p{Nd}+");
+        assert_eq!(regex("letter(script=\"Cyrillic\")"), r"\p{Cyrillic}");
+        assert_eq!(
+            regex_for("letter(script=\"Cyrillic\")", Flavor::JavaScript),
+            Ok(r"/\p{Script=Cyrillic}/u".to_string())
+        );
+        assert_eq!(
+            regex_for(
+                "flags(multiline=True) | letters(unicode=True)",
+                Flavor::JavaScript
+            ),
+            Ok(r"/\p{L}+/mu".to_string())
+        );
+        assert_eq!(
+            regex_for("one_of(\"😀\", \"a\")", Flavor::JavaScript),
+            Ok("/[a😀]/u".to_string())
+        );
+        assert_eq!(
+            regex_for("letters", Flavor::JavaScript),
+            Ok("/[A-Za-z]+/".to_string())
+        );
+        assert_eq!(regex("not(letter(unicode=True))"), r"\P{L}");
+        assert_eq!(
+            regex("one_of(\"-\", letter(unicode=True), number(unicode=True))"),
+            r"[\-\p{L}\p{Nd}]"
+        );
+        assert_eq!(
+            regex_for(
+                "letters(unicode=True) | number(unicode=True)",
+                Flavor::Python
+            ),
+            Ok(r"[^\W\d_]+\d".to_string())
+        );
+        assert_eq!(
+            regex_for("letter(unicode=True, case=\"upper\")", Flavor::Python),
+            Err(ErrorKind::Unsupported {
+                feature: "Unicode properties",
+                flavor: Flavor::Python
+            })
+        );
+        assert_eq!(
+            regex_for("letter(unicode=True) - one_of(\"a\")", Flavor::Pcre),
+            Err(ErrorKind::UncomputableSet)
+        );
+    }
+
+    #[test]
+    fn test_casing() {
+        assert_eq!(regex("letter"), "[A-Za-z]");
+        assert_eq!(regex("letters(case=\"upper\")"), "[A-Z]+");
+        assert_eq!(regex("letters(case=\"lower\")"), "[a-z]+");
+        assert_eq!(regex("letter(case=\"any\", unicode=True)"), r"\p{L}");
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        let input = "ignore_case(literal(\"id-\") | number) | letter(case=\"lower\")";
+
+        assert_eq!(regex(input), "(?i:id-[0-9])[a-z]");
+        assert_eq!(
+            regex_for(input, Flavor::JavaScript),
+            Ok("/[Ii][Dd]-[0-9][a-z]/".to_string())
+        );
+        assert_eq!(
+            regex_for(
+                "ignore_case(literal(\"ab\", many=True) | letter(case=\"upper\"), optional=True)",
+                Flavor::JavaScript
+            ),
+            Ok("/(?:(?:[Aa][Bb])+[A-Za-z])?/".to_string())
+        );
+        assert_eq!(
+            regex_for(
+                "ignore_case(one_of(\"a_\", range(\"x\", \"z\")))",
+                Flavor::JavaScript
+            ),
+            Ok("/[AX-Z_ax-z]/".to_string())
+        );
+        assert_eq!(
+            regex_for(
+                "ignore_case(either(literal(\"é\"), literal(\"ß\")))",
+                Flavor::JavaScript
+            ),
+            Ok("/[Éé]|ß/".to_string())
+        );
+        assert_eq!(
+            regex_for(
+                "group(letter) | ignore_case(same_as(1))",
+                Flavor::JavaScript
+            ),
+            Err(ErrorKind::Unsupported {
+                feature: "case-insensitive backreferences",
+                flavor: Flavor::JavaScript
+            })
+        );
+    }
+
+    #[test]
+    fn test_flags() {
+        let input = "flags(multiline=True, dotall=True) | line_start | glob(rest=True)";
+
+        assert_eq!(regex(input), "(?ms)(?m:^).*");
+        assert_eq!(
+            regex_for("flags(multiline=True)", Flavor::JavaScript),
+            Ok("/(?:)/m".to_string())
+        );
+        assert_eq!(regex_for("", Flavor::JavaScript), Ok("/(?:)/".to_string()));
+        assert_eq!(
+            regex_for(input, Flavor::JavaScript),
+            Ok(r"/(?<![^\n]).*/ms".to_string())
+        );
+        assert_eq!(
+            regex_for(
+                "flags(ignore_case=True) | literal(\"a/b\")",
+                Flavor::JavaScript
+            ),
+            Ok(r"/a\/b/i".to_string())
+        );
+        assert_eq!(
+            regex_for(
+                "flags(unicode=True, ignore_case=True) | letter",
+                Flavor::Pcre
+            ),
+            Ok("(*UTF)(?i)[A-Za-z]".to_string())
+        );
+        assert_eq!(
+            regex_for("flags(unicode=True) | letter", Flavor::Python),
+            Ok("(?u)[A-Za-z]".to_string())
+        );
+        assert_eq!(
+            regex("flags(extended=True) | literal(\"a #1\") | one_of(\" \")"),
+            r"(?x)a\ \#1[\ ]"
+        );
+        assert_eq!(
+            regex_for("flags(extended=True) | letter", Flavor::JavaScript),
+            Err(ErrorKind::Unsupported {
+                feature: "extended mode",
+                flavor: Flavor::JavaScript
+            })
+        );
+
+        let pattern = ast::parse(
+            tokens::tokenize("flags(multiline=True, dotall=True) | letters".to_string()).unwrap(),
+        )
+        .unwrap();
+        let regex = transpile_pattern(&pattern, Flavor::JavaScript).unwrap();
+
+        assert_eq!(regex.pattern, "[A-Za-z]+");
+        assert_eq!(regex.flags, "ms");
+    }
+
+    #[test]
+    fn test_predefined_classes() {
+        assert_eq!(regex("words | whitespaces"), r"\w+\s+");
+        assert_eq!(regex("alnum(many=True)"), "[0-9A-Za-z]+");
+        assert_eq!(regex("hex_digit(select=2)"), "[0-9A-Fa-f]{2}");
+        assert_eq!(regex("punctuation"), r"[!-/:-@\[-`{-~]");
+        assert_eq!(regex("any_char(optional=True)"), r"[\s\S]?");
+        assert_eq!(
+            regex("whitespace(kind=\"space\", select=2) | tab | newline"),
+            r" {2}\t\n"
+        );
+        assert_eq!(regex("not(word) | not(newline)"), r"\W[^\n]");
+        assert_eq!(regex("one_of(word, \"-\")"), r"[\-\w]");
+        assert_eq!(regex("word - one_of(\"_\")"), "[0-9A-Za-z]");
+        assert_eq!(regex("punctuation & none_of(\"[]\")"), r"[!-/:-@\\\^-`{-~]");
+    }
+
+    #[test]
+    fn test_bindings() {
+        assert_eq!(
+            regex("let year = number(select=4); let month = number(select=2); year | literal(\"-\") | month"),
+            "[0-9]{4}-[0-9]{2}"
+        );
+        assert_eq!(
+            regex("let digits = number | number; group(digits, many=True)"),
+            "([0-9][0-9])+"
+        );
+        assert_eq!(
+            regex("let flags = literal(\"f\"); flags(ignore_case=True) | flags"),
+            "(?i)f"
+        );
+    }
+
+    #[test]
+    fn test_definitions() {
+        assert_eq!(
+            regex("def quoted(x) = literal(\"\\\"\") | x | literal(\"\\\"\"); quoted(letters)"),
+            "\"[A-Za-z]+\""
+        );
+        assert_eq!(
+            regex("def between(x, y) = x | glob(rest=True, lazy=True) | y; between(literal(\"(\"), literal(\")\"))"),
+            r"\(.*?\)"
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        let input = "flags(extended=True)
+            | number(select=4) # year
+            | literal(\"- \")
+            # month
+            | number(select=2)";
+
+        assert_eq!(regex(input), "(?x)# year\n[0-9]{4}-\\ \n# month\n[0-9]{2}");
+        assert_eq!(
+            regex(&input.replace("extended", "multiline")),
+            "(?m)[0-9]{4}- [0-9]{2}"
+        );
+        assert_eq!(regex("literal(\"#\") # a hash"), "#");
+    }
+
+    #[test]
+    fn test_repetition() {
+        assert_eq!(
+            regex("repeat(number(select=1..3), times=4, sep=literal(\".\"))"),
+            r"[0-9]{1,3}(?:\.[0-9]{1,3}){3}"
+        );
+        assert_eq!(regex("repeat(literal(\"ab\"), times=2..)"), "(?:ab){2,}");
+        assert_eq!(
+            regex("repeat(number, times=2, sep=whitespace)"),
+            r"[0-9]\s[0-9]"
+        );
+        assert_eq!(
+            regex("separated_by(numbers, sep=literal(\",\"))"),
+            "[0-9]+(?:,[0-9]+)*"
+        );
+        assert_eq!(
+            regex("separated_by(letters, sep=literal(\";\") | whitespace(optional=True), min=0, max=5)"),
+            r"(?:[A-Za-z]+(?:;\s?[A-Za-z]+){0,4})?"
+        );
+    }
+}